@@ -1,158 +1,76 @@
-use crate::models::api::{Message, RequestChatCompletion, ResponseChatCompletion, Role};
-use crate::models::config::{Profile, DEFAULT_MODEL};
-use crate::models::messages::{RawSavedMessage, SavedMessage};
-use crate::path::{
-    get_files_in_dir, get_path_profile_history_dir, get_path_profile_pre_messages_dir,
+use crate::catalogue::Catalogue;
+use crate::models::api::{
+    Message, RequestChatCompletion, ResponseChatCompletion, ResponseListModels, Role,
 };
+use crate::models::config::Profile;
+use crate::models::model_info::ModelInfo;
+use crate::pre_messages::PreMessageOptions;
+use crate::traits::history_store::HistoryStore;
 
 const URL_CHAT_COMPLETION: &str = "https://api.openai.com/v1/chat/completions";
-
-fn get_pre_messages(profile_name: &str) -> Result<Vec<SavedMessage>, String> {
-    let directory = get_path_profile_pre_messages_dir(profile_name)?;
-
-    if !directory.is_dir() {
-        return Ok(Vec::new());
-    }
-
-    let list_path = get_files_in_dir(&directory)?;
-
-    let mut result = Vec::new();
-
-    for path in list_path {
-        let text = crate::fs::load_text(&path)?;
-        if let Some(extension) = path.extension() {
-            let extension = extension
-                .to_str()
-                .ok_or("failed to get string of extname".to_string())?
-                .to_lowercase();
-            if extension == "json" {
-                let mut pre_messages: Vec<SavedMessage> =
-                    serde_json::from_str(&text).map_err(|e| {
-                        format!(
-                            "failed to deserialize json: path={}, err={}",
-                            path.display(),
-                            e
-                        )
-                    })?;
-                result.append(&mut pre_messages);
-                continue;
-            } else if extension == "yaml" || extension == "yml" {
-                let raw_pre_messages: Vec<RawSavedMessage> =
-                    serde_yaml::from_str(&text).map_err(|e| {
-                        format!(
-                            "failed to deserialize yaml: path={}, err={}",
-                            path.display(),
-                            e
-                        )
-                    })?;
-                for message in raw_pre_messages {
-                    result.push(message.try_into()?);
-                }
-                continue;
-            }
-        }
-        let file_name = path
-            .file_name()
-            .ok_or("failed to get filename".to_string())?
-            .to_str()
-            .ok_or("failed to get string of filename".to_string())?
-            .to_lowercase();
-
-        if file_name.contains("system") {
-            result.push(SavedMessage::System(text));
-        } else if file_name.contains("assistant") {
-            result.push(SavedMessage::Assistant(text));
-        } else if file_name.contains("user") {
-            result.push(SavedMessage::User(text))
-        }
-    }
-
-    Ok(result)
-}
-
-fn get_histories(profile_name: &str) -> Result<Vec<SavedMessage>, String> {
-    let mut result = Vec::new();
-    let directory = get_path_profile_history_dir(profile_name)?;
-
-    let list_path = get_files_in_dir(&directory)?;
-
-    for path in list_path {
-        let text = crate::fs::load_text(&path)?;
-
-        let histories: Vec<RawSavedMessage> = serde_yaml::from_str(&text).map_err(|e| {
-            format!(
-                "failed to deserialize yaml: path={}, err={}",
-                path.display(),
-                e
-            )
-        })?;
-
-        for message in histories {
-            result.push(message.try_into()?);
-        }
-    }
-
-    Ok(result)
-}
-
-fn save_history(
-    profile_name: &str,
+const URL_MODELS: &str = "https://api.openai.com/v1/models";
+/// rough tokens taken by the chat format for each message
+const TOKENS_PER_MESSAGE: u64 = 4;
+/// requests answering tool calls sent for a message, so that a model repeating calls stops
+const MAX_TOOL_ROUNDS: usize = 10;
+
+pub fn call_chat_completion(
+    store: &dyn HistoryStore,
+    catalogue: &Catalogue,
+    profile: &Profile,
+    session_name: Option<&str>,
+    pre_message_options: &PreMessageOptions,
+    tool_choice: Option<&str>,
     message: &str,
-    response: &ResponseChatCompletion,
 ) -> Result<(), String> {
-    let directory = get_path_profile_history_dir(profile_name)?;
-    let path = directory.join(format!("{}.yaml", response.created));
-
-    let answer = response.get_assistant_message();
-
-    let history: Vec<RawSavedMessage> = vec![
-        RawSavedMessage {
-            system: None,
-            assistant: None,
-            user: Some(message.to_string()),
-        },
-        RawSavedMessage {
-            system: None,
-            assistant: Some(answer),
-            user: None,
-        },
-    ];
-
-    let text =
-        serde_yaml::to_string(&history).map_err(|e| format!("failed to serialize yaml: {e}"))?;
-
-    crate::fs::save_text(&path, &text)
-}
-
-pub fn call_chat_completion(profile: &Profile, message: &str) -> Result<(), String> {
-    let mut messages = Vec::new();
+    let tools = crate::tools::get_tools(profile)?;
+    if !tools.is_empty()
+        && catalogue
+            .find(&profile.get_model())
+            .is_some_and(|m| m.supports_tools == Some(false))
+    {
+        return Err(format!(
+            "model \"{}\" does not support tools, but tools are declared: path={}",
+            profile.get_model(),
+            crate::path::get_path_profile_tools_dir(&profile.name)?.display()
+        ));
+    }
+    let tool_choice = match tool_choice {
+        Some(choice) if tools.is_empty() => {
+            return Err(format!(
+                "--tool-choice \"{choice}\" is given, but no tools are declared: path={}",
+                crate::path::get_path_profile_tools_dir(&profile.name)?.display()
+            ));
+        }
+        Some(choice) => Some(crate::tools::parse_tool_choice(choice, &tools)?),
+        None => None,
+    };
 
-    if profile.get_use_pre_messages() {
-        let mut pre_messages = get_pre_messages(&profile.name)?
+    let mut messages: Vec<Message> =
+        crate::pre_messages::get_pre_messages(profile, pre_message_options)?
             .iter()
             .map(|m| m.into())
             .collect();
-        messages.append(&mut pre_messages);
-    }
 
-    if profile.get_use_history() {
-        let mut histories = get_histories(&profile.name)?
+    let mut histories: Vec<Message> = if profile.get_use_history() {
+        crate::history::get_histories_for_request(store, profile, session_name)?
             .iter()
             .map(|m| m.into())
-            .collect();
-        messages.append(&mut histories);
-    }
+            .collect()
+    } else {
+        Vec::new()
+    };
 
-    messages.push(Message {
-        role: Role::User,
-        content: message.to_string(),
-    });
+    messages.push(Message::new(Role::User, message));
+    if let Some(info) = catalogue.find(&profile.get_model()) {
+        trim_histories(info, profile, &messages, &mut histories)?;
+    }
+    let user_message = messages.pop().unwrap();
+    messages.append(&mut histories);
+    messages.push(user_message);
 
-    let request = RequestChatCompletion {
-        model: match &profile.model {
-            Some(model) => model.to_string(),
-            None => DEFAULT_MODEL.to_string(),
-        },
+    let mut request = RequestChatCompletion {
+        model: profile.get_model(),
         messages,
         temperature: profile.temperature,
         top_p: profile.top_p,
@@ -163,15 +81,114 @@ pub fn call_chat_completion(profile: &Profile, message: &str) -> Result<(), Stri
             Some(user) => Some(user.clone()),
             None => Some(profile.name.clone()),
         },
+        tools: match tools.is_empty() {
+            true => None,
+            false => Some(tools.iter().map(|t| t.to_tool()).collect()),
+        },
+        tool_choice,
     };
 
+    let session_name = session_name.filter(|_| profile.get_use_history());
+    let mut rounds = 0;
+    // tool calls and their results are sent back until the model answers,
+    // and only the message and the answer are saved to the history
+    let response = loop {
+        let response = send_chat_completion(profile, &request)?;
+        crate::history::save_usage(store, &profile.name, session_name, &response)?;
+
+        let tool_calls = response.get_tool_calls().to_vec();
+        if tool_calls.is_empty() {
+            break response;
+        }
+        if rounds == MAX_TOOL_ROUNDS {
+            return Err(format!(
+                "model kept calling tools after {MAX_TOOL_ROUNDS} rounds of results"
+            ));
+        }
+        rounds += 1;
+
+        let assistant_message = response.choices[0].message.clone();
+        if !assistant_message.get_content().is_empty() {
+            println!("\n{}\n", assistant_message.get_content());
+        }
+        request.messages.push(assistant_message);
+        for tool_call in &tool_calls {
+            let result = crate::tools::run_tool(&tools, tool_call)?;
+            request
+                .messages
+                .push(Message::tool_result(tool_call, &result));
+        }
+        // a forced tool choice would make the model call tools forever
+        request.tool_choice = None;
+    };
+
+    if profile.get_use_history() {
+        crate::history::save_history(store, &profile.name, session_name, message, &response)?;
+    }
+
+    println!("\n{}\n", response.get_assistant_message());
+    Ok(())
+}
+
+fn count_tokens(messages: &[Message]) -> u64 {
+    messages
+        .iter()
+        .map(|m| crate::history::estimate_tokens(m.get_content()) + TOKENS_PER_MESSAGE)
+        .sum()
+}
+
+/// Drop the oldest histories until the request fits in the context window of the model,
+/// leaving `max_tokens` for the completion. `fixed` are the messages which are always sent.
+fn trim_histories(
+    info: &ModelInfo,
+    profile: &Profile,
+    fixed: &[Message],
+    histories: &mut Vec<Message>,
+) -> Result<(), String> {
+    let Some(context_window) = info.context_window else {
+        return Ok(());
+    };
+    let budget = context_window.saturating_sub(profile.max_tokens.unwrap_or(0));
+    let fixed_tokens = count_tokens(fixed);
+    if fixed_tokens > budget {
+        let reserved = profile
+            .max_tokens
+            .map(|n| format!(", {n} reserved for max_tokens"))
+            .unwrap_or_default();
+        return Err(format!(
+            "pre messages and the message (about {fixed_tokens} tokens) do not fit in the context window of \"{}\" ({context_window} tokens{reserved})",
+            info.name
+        ));
+    }
+
+    let count = histories.len();
+    while !histories.is_empty() && fixed_tokens + count_tokens(histories) > budget {
+        histories.remove(0);
+    }
+    // do not start the history with a reply whose question was dropped
+    if histories.len() < count
+        && matches!(histories.first(), Some(m) if matches!(m.role, Role::Assistant))
+    {
+        histories.remove(0);
+    }
+    if histories.len() < count {
+        eprintln!(
+            "warning: dropped {} oldest history messages to fit in the context window of \"{}\" ({context_window} tokens)",
+            count - histories.len(),
+            info.name
+        );
+    }
+    Ok(())
+}
+
+pub fn send_chat_completion(
+    profile: &Profile,
+    request: &RequestChatCompletion,
+) -> Result<ResponseChatCompletion, String> {
     let body =
-        serde_json::to_string(&request).map_err(|e| format!("failed to serialize json: {e}"))?;
+        serde_json::to_string(request).map_err(|e| format!("failed to serialize json: {e}"))?;
 
-    let token = profile
-        .api_key
-        .clone()
-        .ok_or("failed to get token (token is empty)")?;
+    let token = profile.get_api_key()?;
 
     let mut request = ureq::post(URL_CHAT_COMPLETION)
         .set("Content-Type", "application/json")
@@ -201,13 +218,31 @@ pub fn call_chat_completion(profile: &Profile, message: &str) -> Result<(), Stri
         .unwrap()
         .into_string()
         .map_err(|e| format!("failed to parse response body: {e}"))?;
-    let response: ResponseChatCompletion =
-        serde_json::from_str(&text).map_err(|e| format!("failed to deserialize json: {e}"))?;
+    serde_json::from_str(&text).map_err(|e| format!("failed to deserialize json: {e}"))
+}
 
-    if profile.get_use_history() {
-        save_history(&profile.name, message, &response)?;
+/// Ids of models available to `profile`.
+pub fn list_models(profile: &Profile) -> Result<Vec<String>, String> {
+    let token = profile.get_api_key()?;
+
+    let mut request =
+        ureq::get(URL_MODELS).set("Authorization", format!("Bearer {token}").as_ref());
+    if let Some(organization_id) = &profile.organization_id {
+        request = request.set("OpenAI-Organization", organization_id);
     }
 
-    println!("\n{}\n", response.get_assistant_message());
-    Ok(())
+    let response = request.call().map_err(|err| match err {
+        ureq::Error::Status(code, response) => format!(
+            "failed to list models: code={}, body={}",
+            code,
+            response.into_string().unwrap_or_default()
+        ),
+        ureq::Error::Transport(e) => format!("failed to list models: {e}"),
+    })?;
+    let text = response
+        .into_string()
+        .map_err(|e| format!("failed to parse response body: {e}"))?;
+    let response: ResponseListModels =
+        serde_json::from_str(&text).map_err(|e| format!("failed to deserialize json: {e}"))?;
+    Ok(response.data.into_iter().map(|m| m.id).collect())
 }
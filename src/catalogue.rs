@@ -0,0 +1,212 @@
+use crate::models::config::{Config, Profile};
+use crate::models::model_info::{FetchedModels, ModelInfo};
+use serde::Deserialize;
+use std::path::PathBuf;
+
+/// models bundled with the binary
+const BUNDLED_MODELS: &str = include_str!("models/model_catalogue.toml");
+
+#[derive(Deserialize)]
+struct BundledModels {
+    model: Vec<ModelInfo>,
+}
+
+/// cached ids of models are fetched again by `kabeuchi models` after this
+pub const MODELS_CACHE_TTL_SECS: u64 = 24 * 60 * 60;
+
+/// Models known to kabeuchi: the bundled ones, ids fetched from `/v1/models` with any profile
+/// and `[[model]]` of the config, latter ones taking precedence.
+#[derive(Debug, Clone)]
+pub struct Catalogue {
+    pub models: Vec<ModelInfo>,
+    pub fetched: Vec<FetchedModels>,
+}
+
+impl Catalogue {
+    pub fn load(config: Option<&Config>) -> Result<Catalogue, String> {
+        let bundled: BundledModels = toml::from_str(BUNDLED_MODELS)
+            .map_err(|e| format!("failed to deserialize bundled models: {e}"))?;
+        let mut catalogue = Catalogue {
+            models: bundled.model,
+            fetched: load_all_fetched_models()?,
+        };
+
+        let fetched_ids: Vec<String> = catalogue
+            .fetched
+            .iter()
+            .flat_map(|f| f.ids.clone())
+            .collect();
+        // snapshots of known models keep the information of the base model
+        for id in fetched_ids {
+            if catalogue.find(&id).is_none() {
+                catalogue.models.push(ModelInfo::new(&id));
+            }
+        }
+
+        for model in config.map(|c| c.model.as_slice()).unwrap_or_default() {
+            match catalogue.models.iter_mut().find(|m| m.name == model.name) {
+                Some(known) => known.merge(model),
+                None => catalogue.models.push(model.clone()),
+            }
+        }
+        Ok(catalogue)
+    }
+
+    fn get(&self, name: &str) -> Option<&ModelInfo> {
+        self.models.iter().find(|m| m.name == name)
+    }
+
+    /// The model named `name`, or the base model of a snapshot such as `gpt-4o-2024-08-06`.
+    pub fn find(&self, name: &str) -> Option<&ModelInfo> {
+        self.get(name).or_else(|| {
+            self.models
+                .iter()
+                .filter(|m| {
+                    name.strip_prefix(m.name.as_str())
+                        .is_some_and(is_snapshot_suffix)
+                })
+                .max_by_key(|m| m.name.len())
+        })
+    }
+
+    pub fn validate(&self, name: &str) -> Result<&ModelInfo, String> {
+        self.find(name).ok_or_else(|| {
+            let names = self.models.iter().map(|m| m.name.as_str());
+            let hint = match crate::migration::did_you_mean(name, names) {
+                Some(candidate) => format!(" (did you mean \"{candidate}\"?)"),
+                None => "".to_string(),
+            };
+            format!(
+                "unknown model \"{name}\"{hint}; add it as [[model]] to config.toml or run `kabeuchi models` to fetch available models"
+            )
+        })
+    }
+
+    /// Reject unknown models of `profile` and `max_tokens` over the limit of the model,
+    /// and warn on deprecated ones.
+    pub fn validate_profile(&self, profile: &Profile) -> Result<(), String> {
+        for model in [&profile.model, &profile.summary_model]
+            .into_iter()
+            .flatten()
+        {
+            warn_deprecated(self.validate(model)?, model);
+        }
+        let info = self.find(&profile.get_model());
+        if let (Some(max_tokens), Some(limit)) =
+            (profile.max_tokens, info.and_then(|m| m.max_output_tokens))
+        {
+            if max_tokens > limit {
+                return Err(format!(
+                    "max_tokens {max_tokens} exceeds max output tokens of \"{}\" ({limit})",
+                    profile.get_model()
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Warn on unknown and deprecated models of `profile`, without failing.
+    pub fn warn_profile(&self, profile: &Profile) {
+        let summary_model = profile.summary_model.clone();
+        for model in [Some(profile.get_model()), summary_model].iter().flatten() {
+            match self.validate(model) {
+                Ok(info) => warn_deprecated(info, model),
+                Err(e) => eprintln!("warning: {e}"),
+            }
+        }
+    }
+}
+
+/// `-0613` or `-2024-08-06`; other suffixes such as `-nano` or `-preview` are other models.
+fn is_snapshot_suffix(rest: &str) -> bool {
+    let is_digits = |s: &str, len: usize| s.len() == len && s.bytes().all(|b| b.is_ascii_digit());
+    let parts: Vec<&str> = rest.split('-').collect();
+    match parts.as_slice() {
+        ["", date] => is_digits(date, 4),
+        ["", year, month, day] => is_digits(year, 4) && is_digits(month, 2) && is_digits(day, 2),
+        _ => false,
+    }
+}
+
+fn warn_deprecated(info: &ModelInfo, model: &str) {
+    if !info.is_deprecated() {
+        return;
+    }
+    match &info.replacement {
+        Some(replacement) => {
+            eprintln!("warning: model \"{model}\" is deprecated; use \"{replacement}\" instead")
+        }
+        None => eprintln!("warning: model \"{model}\" is deprecated"),
+    }
+}
+
+/// A broken cache is ignored, as it can be fetched again.
+fn load_fetched_models_file(path: &PathBuf) -> Result<Option<FetchedModels>, String> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let text = crate::fs::load_text(path)?;
+    Ok(serde_json::from_str(&text).ok())
+}
+
+fn load_all_fetched_models() -> Result<Vec<FetchedModels>, String> {
+    let mut result = Vec::new();
+    for path in crate::path::get_files_in_dir(&crate::path::get_path_models_cache_dir()?)? {
+        result.extend(load_fetched_models_file(&path)?);
+    }
+    Ok(result)
+}
+
+/// Cached ids of models available to the profile.
+pub fn load_fetched_models(profile_name: &str) -> Result<Option<FetchedModels>, String> {
+    load_fetched_models_file(&crate::path::get_path_profile_models_cache_file(
+        profile_name,
+    )?)
+}
+
+/// Fetch ids of models available to `profile` from `/v1/models` and cache them.
+pub fn refresh(profile: &Profile) -> Result<FetchedModels, String> {
+    let mut ids = crate::api::list_models(profile)?;
+    ids.sort();
+    let fetched = FetchedModels {
+        fetched_at: chrono::Utc::now().timestamp() as u64,
+        profile: profile.name.clone(),
+        ids,
+    };
+    let text = serde_json::to_string_pretty(&fetched)
+        .map_err(|e| format!("failed to serialize models: {e}"))?;
+    crate::fs::save_text(
+        &crate::path::get_path_profile_models_cache_file(&profile.name)?,
+        &text,
+    )?;
+    Ok(fetched)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bundled() -> Catalogue {
+        let bundled: BundledModels = toml::from_str(BUNDLED_MODELS).unwrap();
+        Catalogue {
+            models: bundled.model,
+            fetched: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn find_snapshot_of_known_model() {
+        let catalogue = bundled();
+        assert_eq!(catalogue.find("gpt-4o-2024-08-06").unwrap().name, "gpt-4o");
+        assert_eq!(catalogue.find("gpt-4-0613").unwrap().name, "gpt-4");
+        assert_eq!(catalogue.find("gpt-4-32k-0613").unwrap().name, "gpt-4-32k");
+    }
+
+    #[test]
+    fn find_rejects_other_models_with_known_prefix() {
+        let catalogue = bundled();
+        assert!(catalogue.find("gpt-4.1-nano").is_none());
+        assert!(catalogue.find("gpt-4-1106-preview").is_none());
+        assert!(catalogue.find("gpt-3.5-turbo-instruct").is_none());
+    }
+}
@@ -0,0 +1,159 @@
+use crate::catalogue::Catalogue;
+use crate::models::config::Config;
+use crate::models::model_info::ModelInfo;
+use crate::traits::command_definition::CommandDefinition;
+use clap::{arg, ArgMatches, Command};
+
+pub struct CmdCatalogue;
+struct SubCmdList;
+struct SubCmdShow;
+struct SubCmdRefresh;
+
+const ID_PROFILE: &str = "profile";
+const ID_MODEL: &str = "model";
+
+impl CommandDefinition for CmdCatalogue {
+    const NAME: &'static str = "catalogue";
+
+    fn command() -> Command {
+        Command::new(Self::NAME)
+            .about("show the model catalogue (context window, pricing and capabilities)")
+            .subcommand_required(true)
+            .arg_required_else_help(true)
+            .subcommand(SubCmdList::command())
+            .subcommand(SubCmdShow::command())
+            .subcommand(SubCmdRefresh::command())
+    }
+
+    fn run(args: &ArgMatches) -> Result<(), String> {
+        match args.subcommand() {
+            Some((SubCmdList::NAME, sub_args)) => SubCmdList::run(sub_args),
+            Some((SubCmdShow::NAME, sub_args)) => SubCmdShow::run(sub_args),
+            Some((SubCmdRefresh::NAME, sub_args)) => SubCmdRefresh::run(sub_args),
+            _ => unreachable!("This is Bug."),
+        }
+    }
+}
+
+fn load_catalogue() -> Result<Catalogue, String> {
+    Catalogue::load(Config::load()?.as_ref())
+}
+
+fn format_option<T: ToString>(value: Option<T>) -> String {
+    value.map(|v| v.to_string()).unwrap_or("-".to_string())
+}
+
+impl SubCmdList {
+    const ID_ALL: &'static str = "all";
+}
+
+impl CommandDefinition for SubCmdList {
+    const NAME: &'static str = "list";
+
+    fn command() -> Command {
+        Command::new(Self::NAME)
+            .about("show models of the catalogue")
+            .arg(arg!(-a --all "also show deprecated models"))
+    }
+
+    fn run(args: &ArgMatches) -> Result<(), String> {
+        let is_all = args.get_flag(Self::ID_ALL);
+        let catalogue = load_catalogue()?;
+
+        let mut models: Vec<&ModelInfo> = catalogue
+            .models
+            .iter()
+            .filter(|m| is_all || !m.is_deprecated())
+            .collect();
+        models.sort_by(|a, b| a.name.cmp(&b.name));
+
+        println!(
+            "{:<24} {:>9} {:>9} {:>9} {:>9}  CAPABILITIES",
+            "NAME", "CONTEXT", "OUTPUT", "IN $/1M", "OUT $/1M"
+        );
+        for model in models {
+            let deprecated = if model.is_deprecated() {
+                " (deprecated)"
+            } else {
+                ""
+            };
+            println!(
+                "{:<24} {:>9} {:>9} {:>9} {:>9}  {}{}",
+                model.name,
+                format_option(model.context_window),
+                format_option(model.max_output_tokens),
+                format_option(model.input_price),
+                format_option(model.output_price),
+                model.describe_capabilities(),
+                deprecated
+            );
+        }
+        if !catalogue.fetched.is_empty() {
+            println!();
+        }
+        for fetched in &catalogue.fetched {
+            println!(
+                "{} models fetched with profile \"{}\" at {}",
+                fetched.ids.len(),
+                fetched.profile,
+                fetched.format_fetched_at()
+            );
+        }
+        Ok(())
+    }
+}
+
+impl CommandDefinition for SubCmdShow {
+    const NAME: &'static str = "show";
+
+    fn command() -> Command {
+        Command::new(Self::NAME)
+            .about("show a model of the catalogue as TOML")
+            .arg(arg!(<MODEL>).id(ID_MODEL))
+    }
+
+    fn run(args: &ArgMatches) -> Result<(), String> {
+        let name: &String = args.get_one(ID_MODEL).unwrap();
+        let catalogue = load_catalogue()?;
+        let model = catalogue.validate(name)?;
+
+        if &model.name != name {
+            println!("# snapshot of {}", model.name);
+        }
+        print!(
+            "{}",
+            toml::to_string_pretty(model).map_err(|e| format!("failed to serialize model: {e}"))?
+        );
+        Ok(())
+    }
+}
+
+impl CommandDefinition for SubCmdRefresh {
+    const NAME: &'static str = "refresh";
+
+    fn command() -> Command {
+        Command::new(Self::NAME)
+            .about("fetch models available to the profile from the API")
+            .arg(
+                arg!(<PROFILE_NAME>)
+                    .id(ID_PROFILE)
+                    .long(ID_PROFILE)
+                    .short('p')
+                    .required(false)
+                    .env(crate::env::KABEUCHI_PROFILE)
+                    .default_value("default"),
+            )
+    }
+
+    fn run(args: &ArgMatches) -> Result<(), String> {
+        let profile_name: &String = args.get_one(ID_PROFILE).unwrap();
+        let profile = Config::load()?
+            .ok_or("not found config file")?
+            .get_profile(profile_name, true)?
+            .ok_or(format!("Profile \"{profile_name}\" is not exists"))?;
+
+        let fetched = crate::catalogue::refresh(&profile)?;
+        println!("fetched {} models", fetched.ids.len());
+        Ok(())
+    }
+}
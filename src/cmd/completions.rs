@@ -0,0 +1,29 @@
+use crate::traits::command_definition::CommandDefinition;
+use clap::{arg, ArgMatches, Command};
+
+pub struct CmdCompletions;
+
+impl CmdCompletions {
+    const ID_SHELL: &'static str = "shell";
+}
+
+impl CommandDefinition for CmdCompletions {
+    const NAME: &'static str = "completions";
+
+    fn command() -> Command {
+        Command::new(Self::NAME)
+            .about("print the shell completion script (e.g. `source <(kabeuchi completions bash)`)")
+            .arg(
+                arg!(<SHELL>)
+                    .id(Self::ID_SHELL)
+                    .value_parser(crate::completion::SHELLS),
+            )
+    }
+
+    fn run(args: &ArgMatches) -> Result<(), String> {
+        let shell: &String = args.get_one(Self::ID_SHELL).unwrap();
+        let command = crate::build_command();
+        let bin = command.get_bin_name().unwrap_or(command.get_name());
+        crate::completion::write_registration(shell, bin, &mut std::io::stdout())
+    }
+}
@@ -0,0 +1,169 @@
+use crate::catalogue::Catalogue;
+use crate::models::config::{validate_profile_name, Config, Profile};
+use crate::models::profile_field::{find_field, mask_secret, PROFILE_FIELDS};
+use crate::traits::command_definition::CommandDefinition;
+use clap::{arg, ArgMatches, Command};
+
+pub struct CmdConfig;
+struct SubCmdGet;
+struct SubCmdSet;
+struct SubCmdUnset;
+
+const ID_PROFILE: &str = "profile";
+const ID_KEY: &str = "key";
+const ID_VALUE: &str = "value";
+/// keys checked against the model catalogue by `set`
+const MODEL_KEYS: &[&str] = &["model", "summary_model", "max_tokens"];
+
+fn args_profile_key() -> [clap::Arg; 2] {
+    let keys: Vec<&str> = PROFILE_FIELDS.iter().map(|f| f.key).collect();
+    [
+        arg!(<PROFILE_NAME>).id(ID_PROFILE),
+        arg!(<KEY>)
+            .id(ID_KEY)
+            .help(format!("{}, vars.NAME", keys.join(", "))),
+    ]
+}
+
+impl CommandDefinition for CmdConfig {
+    const NAME: &'static str = "config";
+
+    fn command() -> Command {
+        Command::new(Self::NAME)
+            .about("get or edit a field of profile")
+            .subcommand_required(true)
+            .arg_required_else_help(true)
+            .subcommand(SubCmdGet::command())
+            .subcommand(SubCmdSet::command())
+            .subcommand(SubCmdUnset::command())
+    }
+
+    fn run(args: &ArgMatches) -> Result<(), String> {
+        match args.subcommand() {
+            Some((SubCmdGet::NAME, sub_args)) => SubCmdGet::run(sub_args),
+            Some((SubCmdSet::NAME, sub_args)) => SubCmdSet::run(sub_args),
+            Some((SubCmdUnset::NAME, sub_args)) => SubCmdUnset::run(sub_args),
+            _ => unreachable!("This is Bug."),
+        }
+    }
+}
+
+impl SubCmdGet {
+    const ID_SHOW_SECRET: &'static str = "show-secret";
+}
+
+impl CommandDefinition for SubCmdGet {
+    const NAME: &'static str = "get";
+
+    fn command() -> Command {
+        Command::new(Self::NAME)
+            .about("print a field of profile as written in config file")
+            .args(args_profile_key())
+            .arg(arg!(--"show-secret" "print api_key and organization_id without masking"))
+    }
+
+    fn run(args: &ArgMatches) -> Result<(), String> {
+        let profile_name: &String = args.get_one(ID_PROFILE).unwrap();
+        let key: &String = args.get_one(ID_KEY).unwrap();
+        let is_show_secret = args.get_flag(Self::ID_SHOW_SECRET);
+
+        let config = Config::load()?.ok_or("not found config file")?;
+        let profile = config
+            .get_profile(profile_name, false)?
+            .ok_or(format!("Profile \"{profile_name}\" is not exists"))?;
+
+        let value = profile.get_value(key)?.ok_or(format!(
+            "\"{key}\" is not set in profile \"{profile_name}\""
+        ))?;
+        let is_secret = find_field(key).is_some_and(|f| f.is_secret);
+        if is_secret && !is_show_secret {
+            println!("{}", mask_secret(&value));
+        } else {
+            println!("{value}");
+        }
+        Ok(())
+    }
+}
+
+impl CommandDefinition for SubCmdSet {
+    const NAME: &'static str = "set";
+
+    fn command() -> Command {
+        Command::new(Self::NAME)
+            .about("set a field of profile, creating the profile if it does not exist")
+            .args(args_profile_key())
+            .arg(arg!(<VALUE>).id(ID_VALUE))
+    }
+
+    fn run(args: &ArgMatches) -> Result<(), String> {
+        let profile_name: &String = args.get_one(ID_PROFILE).unwrap();
+        let key: &String = args.get_one(ID_KEY).unwrap();
+        let value: &String = args.get_one(ID_VALUE).unwrap();
+        validate_profile_name(profile_name)?;
+
+        // validate before taking the config lock
+        let config = Config::load()?.unwrap_or_else(Config::new);
+        let mut profile = config
+            .get_profile(profile_name, false)?
+            .unwrap_or_else(|| Profile::new(profile_name));
+        profile.set_value(key, value)?;
+        if MODEL_KEYS.contains(&key.as_str()) {
+            let mut list = config.profile.clone();
+            list.retain(|p| &p.name != profile_name);
+            list.push(profile.clone());
+            Catalogue::load(Some(&config))?.validate_profile(&profile.inherit(&list)?)?;
+        }
+
+        let mut result = Ok(());
+        Config::update(|config| {
+            let mut profile = config
+                .profile
+                .iter()
+                .find(|p| &p.name == profile_name)
+                .cloned()
+                .unwrap_or_else(|| Profile::new(profile_name));
+            result = profile.set_value(key, value);
+            if result.is_ok() {
+                config.upsert_profile(profile);
+            }
+        })?;
+        result
+    }
+}
+
+impl CommandDefinition for SubCmdUnset {
+    const NAME: &'static str = "unset";
+
+    fn command() -> Command {
+        Command::new(Self::NAME)
+            .about("remove a field from profile")
+            .args(args_profile_key())
+    }
+
+    fn run(args: &ArgMatches) -> Result<(), String> {
+        let profile_name: &String = args.get_one(ID_PROFILE).unwrap();
+        let key: &String = args.get_one(ID_KEY).unwrap();
+
+        let config = Config::load()?.ok_or("not found config file")?;
+        config
+            .get_profile(profile_name, false)?
+            .ok_or(format!("Profile \"{profile_name}\" is not exists"))?
+            .unset_value(key)?;
+
+        let mut result = Ok(());
+        Config::update(|config| {
+            if let Some(mut profile) = config
+                .profile
+                .iter()
+                .find(|p| &p.name == profile_name)
+                .cloned()
+            {
+                result = profile.unset_value(key);
+                if result.is_ok() {
+                    config.upsert_profile(profile);
+                }
+            }
+        })?;
+        result
+    }
+}
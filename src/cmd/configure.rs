@@ -1,34 +1,64 @@
+use crate::catalogue::Catalogue;
 use crate::input::{get_confirm, get_input_number, get_input_with_null};
-use crate::models::config::{Config, Profile, DEFAULT_MODEL};
+use crate::models::config::{validate_profile_name, Config, Profile, DEFAULT_MODEL};
+use crate::models::profile_field::{mask_secret, PROFILE_FIELDS};
 use crate::traits::command_definition::CommandDefinition;
-use clap::{arg, ArgMatches, Command};
+use clap::{arg, Arg, ArgAction, ArgMatches, Command};
+use std::collections::BTreeMap;
 use std::fmt::Display;
+use std::path::PathBuf;
 use std::str::FromStr;
 
 pub struct CmdConfigure;
 
 impl CmdConfigure {
     const ID_PROFILE: &str = "profile";
+    const ID_NON_INTERACTIVE: &str = "non-interactive";
+    const ID_VAR: &str = "var";
 }
 
 impl CommandDefinition for CmdConfigure {
     const NAME: &'static str = "configure";
 
     fn command() -> Command {
-        Command::new(Self::NAME).about("configure profile").arg(
-            arg!(<PROFILE_NAME>)
-                .id(Self::ID_PROFILE)
-                .long(Self::ID_PROFILE)
-                .short('p')
-                .required(false)
-                .default_value("default"),
-        )
+        Command::new(Self::NAME)
+            .about("configure profile")
+            .arg(
+                arg!(<PROFILE_NAME>)
+                    .id(Self::ID_PROFILE)
+                    .long(Self::ID_PROFILE)
+                    .short('p')
+                    .required(false)
+                    .env(crate::env::KABEUCHI_PROFILE)
+                    .default_value("default"),
+            )
+            .arg(
+                arg!(--"non-interactive" "save the values given by flags without asking")
+                    .id(Self::ID_NON_INTERACTIVE),
+            )
+            .args(PROFILE_FIELDS.iter().map(|field| {
+                Arg::new(field.key)
+                    .long(field.key.replace('_', "-"))
+                    .value_name(field.kind.type_name().to_uppercase().replace(' ', "_"))
+                    .required(false)
+                    .help(field.help)
+            }))
+            .arg(
+                arg!(<KEY_VALUE>)
+                    .id(Self::ID_VAR)
+                    .long(Self::ID_VAR)
+                    .required(false)
+                    .action(ArgAction::Append)
+                    .value_parser(crate::template::parse_var)
+                    .help("default value of template variable (KEY=VALUE)"),
+            )
     }
 
     fn run(args: &ArgMatches) -> Result<(), String> {
         let profile_name: &String = args.get_one(Self::ID_PROFILE).unwrap();
+        validate_profile_name(profile_name)?;
 
-        let mut config = if let Some(config) = Config::load()? {
+        let config = if let Some(config) = Config::load()? {
             config
         } else {
             Config::new()
@@ -37,40 +67,131 @@ impl CommandDefinition for CmdConfigure {
         let mut profile = if let Some(profile) = config.get_profile(profile_name, false)? {
             profile
         } else {
-            Profile {
-                name: profile_name.to_string(),
-                source_profile: None,
-                api_key: None,
-                organization_id: None,
-                use_pre_messages: None,
-                use_history: None,
-                model: None,
-                temperature: None,
-                top_p: None,
-                max_tokens: None,
-                presence_penalty: None,
-                frequency_penalty: None,
-                user: None,
-            }
+            Profile::new(profile_name)
         };
 
+        for field in PROFILE_FIELDS {
+            if let Some(value) = args.get_one::<String>(field.key) {
+                profile.set_value(field.key, value)?;
+            }
+        }
+        if let Some(vars) = args.get_many::<(String, String)>(Self::ID_VAR) {
+            profile
+                .vars
+                .get_or_insert_with(BTreeMap::new)
+                .extend(vars.cloned());
+        }
+
+        let catalogue = Catalogue::load(Some(&config))?;
+        if args.get_flag(Self::ID_NON_INTERACTIVE) {
+            catalogue.validate_profile(&profile.inherit(&with_profile(&config, &profile))?)?;
+            Config::update(|config| config.upsert_profile(profile))?;
+            return Ok(());
+        }
+
         println!("profile name: {profile_name}");
-        profile.api_key =
-            input_str_with_null_and_default("OpenAI API key", &profile.api_key, true)?;
+        profile.source_profile = input_str_with_null_and_default(
+            "source profile (empty: none)",
+            &profile.source_profile,
+            false,
+        )?;
+
+        // values of source profiles are shown as defaults, and kept inherited when unchanged
+        let mut inherited = Profile::new(profile_name);
+        inherited.source_profile = profile.source_profile.clone();
+        let inherited = inherited.inherit(&with_profile(&config, &profile))?;
+        if !inherited.resolved_sources.is_empty() {
+            println!(
+                "inherited from {}:",
+                inherited.resolved_sources.join(" -> ")
+            );
+            for field in PROFILE_FIELDS.iter().filter(|f| f.key != "source_profile") {
+                if let Some(value) = inherited.get_value(field.key)? {
+                    let value = if field.is_secret {
+                        mask_secret(&value)
+                    } else {
+                        value
+                    };
+                    println!("  {} = {}", field.key, value);
+                }
+            }
+        }
+        let original = profile.clone();
+        profile.merge(&inherited)?;
+
+        profile.api_key = input_str_with_null_and_default(
+            "OpenAI API key (empty: read from another source)",
+            &profile.api_key,
+            true,
+        )?;
+        if profile.api_key.is_none() {
+            profile.api_key_env = input_str_with_null_and_default(
+                "environment variable of API key",
+                &profile.api_key_env,
+                false,
+            )?;
+            profile.api_key_command = input_str_with_null_and_default(
+                "command printing API key",
+                &profile.api_key_command,
+                false,
+            )?;
+            profile.api_key_file = input_str_with_null_and_default(
+                "file of API key",
+                &profile
+                    .api_key_file
+                    .as_ref()
+                    .map(|p| p.display().to_string()),
+                false,
+            )?
+            .map(PathBuf::from);
+        }
         profile.organization_id = input_str_with_null_and_default(
             "OpenAI Organization ID",
             &profile.organization_id,
             true,
         )?;
         profile.use_pre_messages = input_flag(profile.get_use_pre_messages(), "use pre messages")?;
+        if profile.get_use_pre_messages() && profile.source_profile.is_some() {
+            profile.inherit_pre_messages = input_flag(
+                profile.get_inherit_pre_messages(),
+                "inherit pre messages of source profile",
+            )?;
+        }
         profile.use_history = input_flag(profile.get_use_history(), "use history")?;
+        if profile.get_use_history() {
+            profile.lenient_history =
+                input_flag(profile.get_lenient_history(), "skip broken history files")?;
+            profile.summary_threshold = input_number_with_default(
+                "summarize history over tokens (empty: disabled)",
+                "integer",
+                &profile.summary_threshold,
+            )?;
+            if profile.summary_threshold.is_some() {
+                profile.summary_model = input_str_with_null_and_default(
+                    "summary model",
+                    &profile.summary_model,
+                    false,
+                )?;
+                profile.summary_keep_turns = input_number_with_default(
+                    "recent turns kept without summarizing",
+                    "integer",
+                    &profile.summary_keep_turns,
+                )?;
+            }
+        }
 
         let default_model = if let Some(model) = &profile.model {
             Some(model.to_string())
         } else {
             Some(DEFAULT_MODEL.to_string())
         };
-        profile.model = input_str_with_null_and_default("ChatGPT model", &default_model, false)?;
+        profile.model = loop {
+            let model = input_str_with_null_and_default("ChatGPT model", &default_model, false)?;
+            match model.as_ref().map(|m| catalogue.validate(m)) {
+                Some(Err(e)) => println!("{e}"),
+                _ => break model,
+            }
+        };
 
         let is_default_option = input_is_default_option()?;
 
@@ -90,11 +211,34 @@ impl CommandDefinition for CmdConfigure {
             profile.user = input_str_with_null_and_default("user", &profile.user, false)?;
         }
 
-        config.upsert_profile(profile);
-        config.save()
+        catalogue.validate_profile(&profile)?;
+        for field in PROFILE_FIELDS.iter().filter(|f| f.key != "source_profile") {
+            let value = profile.get_value(field.key)?;
+            if original.get_value(field.key)?.is_none()
+                && value.is_some()
+                && value == inherited.get_value(field.key)?
+            {
+                profile.unset_value(field.key)?;
+            }
+        }
+
+        Config::update(|config| config.upsert_profile(profile))?;
+        Ok(())
     }
 }
 
+/// Profiles of `config` with `profile` replacing the saved one.
+fn with_profile(config: &Config, profile: &Profile) -> Vec<Profile> {
+    let mut result: Vec<Profile> = config
+        .profile
+        .iter()
+        .filter(|p| p.name != profile.name)
+        .cloned()
+        .collect();
+    result.push(profile.clone());
+    result
+}
+
 fn input_str_with_null_and_default<T: Display>(
     message_without_separator: T,
     default_value: &Option<String>,
@@ -102,14 +246,7 @@ fn input_str_with_null_and_default<T: Display>(
 ) -> Result<Option<String>, String> {
     let prefix = if let Some(default_value) = default_value {
         let masked_value = if is_masking_default {
-            format!(
-                "xxxxxxxxxx{}",
-                default_value
-                    .chars()
-                    .enumerate()
-                    .filter(|&(i, _)| default_value.len() - 5 <= i)
-                    .fold("".to_string(), |s, (_, c)| format!("{}{}", s, c))
-            )
+            mask_secret(default_value)
         } else {
             default_value.to_string()
         };
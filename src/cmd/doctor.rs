@@ -0,0 +1,419 @@
+use crate::input::{get_confirm, get_input};
+use crate::models::config::{Config, HistoryBackend};
+use crate::models::messages::{RawSavedMessage, SavedMessage};
+use crate::models::session::Session;
+use crate::models::summary::HistorySummary;
+use crate::path::{
+    get_files_in_dir, get_path_cache_dir, get_path_config_dir, get_path_config_file,
+    get_path_data_dir, get_path_history_db_file, get_path_history_dir, get_path_personas_dir,
+    get_path_pre_messages_dir, get_path_quarantine_dir, get_path_state_dir,
+    get_path_trusted_projects_file, get_path_usage_dir,
+};
+use crate::traits::command_definition::CommandDefinition;
+use clap::{arg, ArgMatches, Command};
+use serde::de::DeserializeOwned;
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+
+pub struct CmdDoctor;
+
+impl CmdDoctor {
+    const ID_CHECK: &'static str = "check";
+    const ID_FIX: &'static str = "fix";
+}
+
+struct Problem {
+    path: Option<PathBuf>,
+    message: String,
+    /// content rewritten with only the readable entries, if any could be salvaged
+    fixed: Option<String>,
+    /// permissions to restrict the path to, when others can access it
+    mode: Option<u32>,
+}
+
+impl CommandDefinition for CmdDoctor {
+    const NAME: &'static str = "doctor";
+
+    fn command() -> Command {
+        Command::new(Self::NAME)
+            .about("validate config, pre messages and histories")
+            .arg(arg!(--check "only report problems without asking to repair").id(Self::ID_CHECK))
+            .arg(
+                arg!(--fix "restrict permissions without asking, and only report other problems")
+                    .id(Self::ID_FIX)
+                    .conflicts_with(Self::ID_CHECK),
+            )
+    }
+
+    fn run(args: &ArgMatches) -> Result<(), String> {
+        let is_check = args.get_flag(Self::ID_CHECK);
+        let is_fix = args.get_flag(Self::ID_FIX);
+
+        let mut problems = Vec::new();
+        let config = check_config(&mut problems)?;
+        check_pre_messages(&mut problems)?;
+        match config.as_ref().map(|c| c.get_history_backend()) {
+            Some(HistoryBackend::Sqlite) => check_history_db(config.as_ref(), &mut problems)?,
+            _ => check_history_files(&mut problems)?,
+        }
+        check_permissions(&mut problems)?;
+
+        if problems.is_empty() {
+            println!("no problems found");
+            return Ok(());
+        }
+
+        for problem in &problems {
+            println!("{}", problem.message);
+        }
+        println!("\n{} problems found", problems.len());
+
+        if is_check {
+            return Err(format!("{} problems found", problems.len()));
+        }
+
+        if is_fix {
+            let mut count = 0;
+            for problem in &problems {
+                match (&problem.path, problem.mode) {
+                    (Some(path), Some(mode)) => {
+                        crate::fs::set_mode(path, mode)?;
+                        println!("restricted permissions to {mode:o}: {}", path.display());
+                    }
+                    _ => count += 1,
+                }
+            }
+            if count > 0 {
+                return Err(format!(
+                    "{count} problems need to be repaired by `kabeuchi doctor`"
+                ));
+            }
+            return Ok(());
+        }
+
+        // answers cannot be asked, and an empty answer must not be taken as yes
+        if !std::io::stdin().is_terminal() {
+            return Err(format!(
+                "{} problems found; run `kabeuchi doctor` in a terminal to repair them, or `kabeuchi doctor --fix` to restrict permissions",
+                problems.len()
+            ));
+        }
+        for problem in &problems {
+            if let Some(path) = &problem.path {
+                repair(path, problem)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn repair(path: &PathBuf, problem: &Problem) -> Result<(), String> {
+    if let Some(mode) = problem.mode {
+        println!("\n{}", path.display());
+        loop {
+            match get_confirm(format!("restrict permissions to {mode:o}? [Y/n]: "), true)? {
+                Some(true) => {
+                    crate::fs::set_mode(path, mode)?;
+                    println!("fixed");
+                }
+                Some(false) => {}
+                None => {
+                    println!("invalid input");
+                    continue;
+                }
+            }
+            return Ok(());
+        }
+    }
+
+    let choices = if problem.fixed.is_some() {
+        "[q]uarantine / [f]ix (drop unreadable entries) / [S]kip"
+    } else {
+        "[q]uarantine / [S]kip"
+    };
+    println!("\n{}", path.display());
+
+    loop {
+        let answer = get_input(format!("{choices}: "))?.to_lowercase();
+        match answer.as_str() {
+            "q" | "quarantine" => {
+                let destination = quarantine(path)?;
+                println!("moved to {}", destination.display());
+                return Ok(());
+            }
+            "f" | "fix" if problem.fixed.is_some() => {
+                crate::fs::save_text(path, problem.fixed.as_ref().unwrap())?;
+                println!("fixed");
+                return Ok(());
+            }
+            "" | "s" | "skip" => return Ok(()),
+            _ => println!("\ninvalid input\n"),
+        }
+    }
+}
+
+fn quarantine(path: &PathBuf) -> Result<PathBuf, String> {
+    let base_dirs = [get_path_config_dir()?, get_path_data_dir()?];
+    let relative = base_dirs
+        .iter()
+        .find_map(|dir| path.strip_prefix(dir).ok())
+        .map(|p| p.to_path_buf())
+        .unwrap_or_else(|| PathBuf::from(path.file_name().unwrap_or_default()));
+    let destination = get_path_quarantine_dir()?.join(relative);
+
+    if let Some(dir) = destination.parent() {
+        crate::fs::create_dir_private(dir).map_err(|e| {
+            format!(
+                "failed to create directory: path={}, err={}",
+                dir.display(),
+                e
+            )
+        })?;
+    }
+    std::fs::rename(path, &destination).map_err(|e| {
+        format!(
+            "failed to move file: from={}, to={}, err={}",
+            path.display(),
+            destination.display(),
+            e
+        )
+    })?;
+    Ok(destination)
+}
+
+fn format_location(path: &Path, e: &serde_yaml::Error) -> String {
+    match e.location() {
+        Some(location) => format!(
+            "{}:{}:{}: {}",
+            path.display(),
+            location.line(),
+            location.column(),
+            e
+        ),
+        None => format!("{}: {}", path.display(), e),
+    }
+}
+
+fn check_yaml<T: DeserializeOwned>(
+    path: &PathBuf,
+    problems: &mut Vec<Problem>,
+) -> Result<(), String> {
+    let text = crate::fs::load_text(path)?;
+    if let Err(e) = serde_yaml::from_str::<T>(&text) {
+        problems.push(Problem {
+            path: Some(path.clone()),
+            message: format_location(path, &e),
+            fixed: None,
+            mode: None,
+        });
+    }
+    Ok(())
+}
+
+fn check_config(problems: &mut Vec<Problem>) -> Result<Option<Config>, String> {
+    let path = get_path_config_file()?;
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let text = crate::fs::load_text(&path)?;
+    let config = match Config::parse(&text) {
+        Ok(config) => config,
+        Err(e) => {
+            // a broken config can not be quarantined because every command needs it
+            problems.push(Problem {
+                path: None,
+                message: format!("{}: {}", path.display(), e.to_string().trim()),
+                fixed: None,
+                mode: None,
+            });
+            return Ok(None);
+        }
+    };
+
+    for profile in &config.profile {
+        if let Err(e) = profile.resolve(&config.profile, None) {
+            problems.push(Problem {
+                path: None,
+                message: format!("{}: profile \"{}\": {}", path.display(), profile.name, e),
+                fixed: None,
+                mode: None,
+            });
+        }
+    }
+
+    Ok(Some(config))
+}
+
+fn get_sub_dirs(path: &PathBuf) -> Result<Vec<PathBuf>, String> {
+    if !path.is_dir() {
+        return Ok(Vec::new());
+    }
+    let mut result = Vec::new();
+    let entries = std::fs::read_dir(path).map_err(|e| format!("failed to read directory: {e}"))?;
+    for entry in entries {
+        let entry =
+            entry.map_err(|e| format!("failed to parse result of reading directory: {e}"))?;
+        if entry.path().is_dir() {
+            result.push(entry.path());
+        }
+    }
+    result.sort();
+    Ok(result)
+}
+
+fn check_pre_messages(problems: &mut Vec<Problem>) -> Result<(), String> {
+    let mut directories = get_sub_dirs(&get_path_pre_messages_dir()?)?;
+    directories.append(&mut get_sub_dirs(&get_path_personas_dir()?)?);
+
+    let root = get_path_config_dir()?;
+    for directory in directories {
+        for path in get_files_in_dir(&directory)? {
+            if let Err(e) = crate::pre_messages::load_pre_message_file(&path, &root) {
+                problems.push(Problem {
+                    path: Some(path),
+                    message: e,
+                    fixed: None,
+                    mode: None,
+                });
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Keep only the entries of a broken turn file that can be read as messages.
+fn salvage_turn(text: &str) -> Option<String> {
+    let value: serde_yaml::Value = serde_yaml::from_str(text).ok()?;
+    let entries = value.as_sequence()?;
+    let messages: Vec<RawSavedMessage> = entries
+        .iter()
+        .filter_map(|v| serde_yaml::from_value::<RawSavedMessage>(v.clone()).ok())
+        .filter_map(|m| SavedMessage::try_from(m).ok())
+        .map(|m| m.into())
+        .collect();
+    if messages.is_empty() {
+        return None;
+    }
+    serde_yaml::to_string(&messages).ok()
+}
+
+fn check_turn_files(directory: &PathBuf, problems: &mut Vec<Problem>) -> Result<(), String> {
+    for path in get_files_in_dir(directory)? {
+        if let Err(e) = crate::store::load_turn_file(&path) {
+            let text = crate::fs::load_text(&path)?;
+            problems.push(Problem {
+                fixed: salvage_turn(&text),
+                mode: None,
+                path: Some(path),
+                message: e,
+            });
+        }
+    }
+
+    let path_summary = directory.join("summary").join("summary.yaml");
+    if path_summary.is_file() {
+        check_yaml::<HistorySummary>(&path_summary, problems)?;
+    }
+    Ok(())
+}
+
+fn check_history_files(problems: &mut Vec<Problem>) -> Result<(), String> {
+    for directory in get_sub_dirs(&get_path_history_dir()?)? {
+        check_turn_files(&directory, problems)?;
+
+        let sessions_dir = directory.join("sessions");
+        for path in get_files_in_dir(&sessions_dir)? {
+            check_yaml::<Session>(&path, problems)?;
+        }
+        for session_dir in get_sub_dirs(&sessions_dir)? {
+            check_turn_files(&session_dir, problems)?;
+        }
+    }
+    Ok(())
+}
+
+fn check_history_db(config: Option<&Config>, problems: &mut Vec<Problem>) -> Result<(), String> {
+    let store = crate::store::open_configured_store(config)?;
+    for profile_name in store.list_profiles()? {
+        let mut session_names = vec![None];
+        let sessions = store.load_sessions(&profile_name)?;
+        session_names.extend(sessions.iter().map(|s| Some(s.name.as_str())));
+
+        for session_name in session_names {
+            let (_, errors) = store.load_turns_lenient(&profile_name, session_name)?;
+            for e in errors {
+                problems.push(Problem {
+                    path: None,
+                    message: e,
+                    fixed: None,
+                    mode: None,
+                });
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Collect `dir` and everything under it.
+fn walk(dir: &Path, result: &mut Vec<PathBuf>) -> Result<(), String> {
+    if !dir.is_dir() {
+        return Ok(());
+    }
+    result.push(dir.to_path_buf());
+    let entries = std::fs::read_dir(dir).map_err(|e| format!("failed to read directory: {e}"))?;
+    for entry in entries {
+        let path = entry
+            .map_err(|e| format!("failed to parse result of reading directory: {e}"))?
+            .path();
+        if path.is_dir() {
+            walk(&path, result)?;
+        } else {
+            result.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// The base dirs, the config file and files holding conversations should be private.
+fn check_permissions(problems: &mut Vec<Problem>) -> Result<(), String> {
+    let mut paths = vec![
+        get_path_config_dir()?,
+        get_path_config_file()?,
+        get_path_data_dir()?,
+        get_path_state_dir()?,
+    ];
+    paths.push(get_path_history_db_file()?);
+    paths.push(get_path_trusted_projects_file()?);
+    walk(&get_path_history_dir()?, &mut paths)?;
+    walk(&get_path_usage_dir()?, &mut paths)?;
+    walk(&get_path_quarantine_dir()?, &mut paths)?;
+    walk(&get_path_cache_dir()?, &mut paths)?;
+    paths.sort();
+    paths.dedup();
+
+    for path in paths {
+        let Some(current) = crate::fs::get_mode(&path) else {
+            continue;
+        };
+        if current & 0o077 == 0 {
+            continue;
+        }
+        let mode = if path.is_dir() {
+            crate::fs::MODE_PRIVATE_DIR
+        } else {
+            crate::fs::MODE_PRIVATE_FILE
+        };
+        problems.push(Problem {
+            message: format!(
+                "{}: accessible by other users (mode {current:o}, expected {mode:o})",
+                path.display()
+            ),
+            path: Some(path),
+            fixed: None,
+            mode: Some(mode),
+        });
+    }
+    Ok(())
+}
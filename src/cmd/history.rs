@@ -0,0 +1,291 @@
+use crate::catalogue::Catalogue;
+use crate::history;
+use crate::models::config::{Config, HistoryBackend};
+use crate::models::session::{Session, MAIN_SESSION};
+use crate::traits::command_definition::CommandDefinition;
+use crate::traits::history_store::HistoryStore;
+use clap::{arg, value_parser, ArgMatches, Command};
+use std::collections::BTreeMap;
+
+pub struct CmdHistory;
+struct SubCmdList;
+struct SubCmdFork;
+struct SubCmdMigrate;
+struct SubCmdUsage;
+
+impl CommandDefinition for CmdHistory {
+    const NAME: &'static str = "history";
+
+    fn command() -> Command {
+        Command::new(Self::NAME)
+            .about("manage conversation histories")
+            .subcommand_required(true)
+            .arg_required_else_help(true)
+            .subcommand(SubCmdList::command())
+            .subcommand(SubCmdFork::command())
+            .subcommand(SubCmdMigrate::command())
+            .subcommand(SubCmdUsage::command())
+    }
+
+    fn run(args: &ArgMatches) -> Result<(), String> {
+        match args.subcommand() {
+            Some((SubCmdList::NAME, sub_args)) => SubCmdList::run(sub_args),
+            Some((SubCmdFork::NAME, sub_args)) => SubCmdFork::run(sub_args),
+            Some((SubCmdMigrate::NAME, sub_args)) => SubCmdMigrate::run(sub_args),
+            Some((SubCmdUsage::NAME, sub_args)) => SubCmdUsage::run(sub_args),
+            _ => unreachable!("This is Bug."),
+        }
+    }
+}
+
+impl SubCmdList {
+    const ID_PROFILE: &'static str = "profile";
+}
+
+impl CommandDefinition for SubCmdList {
+    const NAME: &'static str = "list";
+
+    fn command() -> Command {
+        Command::new(Self::NAME)
+            .about("show sessions of profile as tree")
+            .arg(
+                arg!(<PROFILE_NAME>)
+                    .id(Self::ID_PROFILE)
+                    .long(Self::ID_PROFILE)
+                    .short('p')
+                    .required(false)
+                    .env(crate::env::KABEUCHI_PROFILE)
+                    .default_value("default"),
+            )
+    }
+
+    fn run(args: &ArgMatches) -> Result<(), String> {
+        let profile_name: &String = args.get_one(Self::ID_PROFILE).unwrap();
+
+        let store = crate::store::open_configured_store(Config::load()?.as_ref())?;
+        let store = store.as_ref();
+
+        let sessions = store.load_sessions(profile_name)?;
+        let count = store.load_turns(profile_name, None)?.len();
+        println!("{MAIN_SESSION} ({count} turns)");
+        print_children(store, profile_name, &sessions, MAIN_SESSION, "")?;
+
+        let orphans: Vec<&Session> = sessions
+            .iter()
+            .filter(|s| {
+                s.parent
+                    .as_ref()
+                    .map(|p| !sessions.iter().any(|c| &c.name == p))
+                    .unwrap_or(false)
+            })
+            .collect();
+        for session in orphans {
+            println!(
+                "{} (parent \"{}\" is not exists)",
+                session.name,
+                session.get_parent_name()
+            );
+            print_children(store, profile_name, &sessions, &session.name, "")?;
+        }
+
+        Ok(())
+    }
+}
+
+fn print_children(
+    store: &dyn HistoryStore,
+    profile_name: &str,
+    sessions: &[Session],
+    parent_name: &str,
+    indent: &str,
+) -> Result<(), String> {
+    let children: Vec<&Session> = sessions
+        .iter()
+        .filter(|s| s.get_parent_name() == parent_name)
+        .collect();
+
+    for (i, session) in children.iter().enumerate() {
+        let is_last = i == children.len() - 1;
+        let (branch, next_indent) = if is_last {
+            ("└── ", format!("{indent}    "))
+        } else {
+            ("├── ", format!("{indent}│   "))
+        };
+        let count = store.load_turns(profile_name, Some(&session.name))?.len();
+        println!(
+            "{indent}{branch}{} (forked at turn {}, {count} turns)",
+            session.name, session.forked_at
+        );
+        print_children(store, profile_name, sessions, &session.name, &next_indent)?;
+    }
+
+    Ok(())
+}
+
+impl SubCmdFork {
+    const ID_PROFILE: &'static str = "profile";
+    const ID_FROM: &'static str = "from";
+    const ID_AT: &'static str = "at";
+    const ID_NAME: &'static str = "name";
+}
+
+impl CommandDefinition for SubCmdFork {
+    const NAME: &'static str = "fork";
+
+    fn command() -> Command {
+        Command::new(Self::NAME)
+            .about("create new session seeded with the turns up to <TURN>")
+            .arg(
+                arg!(<PROFILE_NAME>)
+                    .id(Self::ID_PROFILE)
+                    .long(Self::ID_PROFILE)
+                    .short('p')
+                    .required(false)
+                    .env(crate::env::KABEUCHI_PROFILE)
+                    .default_value("default"),
+            )
+            .arg(
+                arg!(<SESSION>)
+                    .id(Self::ID_FROM)
+                    .long(Self::ID_FROM)
+                    .required(false)
+                    .help("session to fork from (default: main history)"),
+            )
+            .arg(
+                arg!(<TURN>)
+                    .id(Self::ID_AT)
+                    .long(Self::ID_AT)
+                    .value_parser(value_parser!(usize)),
+            )
+            .arg(arg!(<NAME>).id(Self::ID_NAME).help("name of new session"))
+    }
+
+    fn run(args: &ArgMatches) -> Result<(), String> {
+        let profile_name: &String = args.get_one(Self::ID_PROFILE).unwrap();
+        let from: Option<&String> = args.get_one(Self::ID_FROM);
+        let turn: &usize = args.get_one(Self::ID_AT).unwrap();
+        let name: &String = args.get_one(Self::ID_NAME).unwrap();
+
+        let from = from.map(|s| s.as_str()).filter(|s| *s != MAIN_SESSION);
+        let _lock =
+            crate::fs::lock_exclusive(&crate::path::get_path_profile_lock_file(profile_name)?)?;
+        let store = crate::store::open_configured_store(Config::load()?.as_ref())?;
+        let session = history::fork_session(store.as_ref(), profile_name, from, name, *turn)?;
+        println!(
+            "created session \"{}\" from \"{}\" at turn {}",
+            session.name,
+            session.get_parent_name(),
+            session.forked_at
+        );
+
+        Ok(())
+    }
+}
+
+impl SubCmdMigrate {
+    const ID_NO_SWITCH: &'static str = "no-switch";
+}
+
+impl CommandDefinition for SubCmdMigrate {
+    const NAME: &'static str = "migrate";
+
+    fn command() -> Command {
+        Command::new(Self::NAME)
+            .about("copy histories, sessions and usages from YAML files into SQLite database")
+            .arg(
+                arg!(--"no-switch" "keep history_backend of config unchanged")
+                    .id(Self::ID_NO_SWITCH),
+            )
+    }
+
+    fn run(args: &ArgMatches) -> Result<(), String> {
+        let is_no_switch = args.get_flag(Self::ID_NO_SWITCH);
+
+        let report = history::migrate_yaml_to_sqlite()?;
+        println!(
+            "migrated {} profiles: {} sessions, {} turns, {} usages",
+            report.profiles, report.sessions, report.turns, report.usages
+        );
+        println!(
+            "database: {}",
+            crate::path::get_path_history_db_file()?.display()
+        );
+
+        if !is_no_switch {
+            Config::update(|config| config.history_backend = Some(HistoryBackend::Sqlite))?;
+            println!("history_backend of config is switched to \"sqlite\"");
+        }
+
+        Ok(())
+    }
+}
+
+impl SubCmdUsage {
+    const ID_PROFILE: &'static str = "profile";
+}
+
+impl CommandDefinition for SubCmdUsage {
+    const NAME: &'static str = "usage";
+
+    fn command() -> Command {
+        Command::new(Self::NAME)
+            .about("show token usage of profile by model, with cost estimated from the model catalogue")
+            .arg(
+                arg!(<PROFILE_NAME>)
+                    .id(Self::ID_PROFILE)
+                    .long(Self::ID_PROFILE)
+                    .short('p')
+                    .required(false)
+                    .env(crate::env::KABEUCHI_PROFILE)
+                    .default_value("default"),
+            )
+    }
+
+    fn run(args: &ArgMatches) -> Result<(), String> {
+        let profile_name: &String = args.get_one(Self::ID_PROFILE).unwrap();
+
+        let config = Config::load()?;
+        let catalogue = Catalogue::load(config.as_ref())?;
+        let store = crate::store::open_configured_store(config.as_ref())?;
+
+        // model -> (requests, prompt tokens, completion tokens)
+        let mut totals: BTreeMap<String, (u64, u64, u64)> = BTreeMap::new();
+        for usage in store.load_usages(profile_name)? {
+            let total = totals.entry(usage.model).or_default();
+            total.0 += 1;
+            total.1 += usage.prompt_tokens as u64;
+            total.2 += usage.completion_tokens as u64;
+        }
+
+        println!(
+            "{:<28} {:>8} {:>12} {:>12} {:>10}",
+            "MODEL", "REQUESTS", "PROMPT", "COMPLETION", "COST($)"
+        );
+        let mut total_cost = 0.0;
+        let mut is_cost_unknown = false;
+        for (model, (requests, prompt, completion)) in &totals {
+            let cost = catalogue
+                .find(model)
+                .and_then(|m| m.estimate_cost(*prompt, *completion));
+            total_cost += cost.unwrap_or(0.0);
+            is_cost_unknown |= cost.is_none();
+            println!(
+                "{:<28} {:>8} {:>12} {:>12} {:>10}",
+                model,
+                requests,
+                prompt,
+                completion,
+                cost.map(|c| format!("{c:.4}")).unwrap_or("-".to_string())
+            );
+        }
+        println!(
+            "\ntotal estimated cost: ${total_cost:.4}{}",
+            if is_cost_unknown {
+                " (excluding models without prices)"
+            } else {
+                ""
+            }
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,43 @@
+use crate::traits::command_definition::CommandDefinition;
+use clap::{arg, value_parser, ArgMatches, Command};
+use std::path::PathBuf;
+
+pub struct CmdMan;
+
+impl CmdMan {
+    const ID_OUT_DIR: &'static str = "out-dir";
+}
+
+impl CommandDefinition for CmdMan {
+    const NAME: &'static str = "man";
+
+    fn command() -> Command {
+        Command::new(Self::NAME)
+            .about("print the man page, or write pages of all subcommands to <DIR>")
+            .arg(
+                arg!(<DIR>)
+                    .id(Self::ID_OUT_DIR)
+                    .long(Self::ID_OUT_DIR)
+                    .short('o')
+                    .required(false)
+                    .value_parser(value_parser!(PathBuf)),
+            )
+    }
+
+    fn run(args: &ArgMatches) -> Result<(), String> {
+        let command = crate::build_command();
+        match args.get_one::<PathBuf>(Self::ID_OUT_DIR) {
+            Some(dir) => {
+                std::fs::create_dir_all(dir)
+                    .map_err(|e| format!("failed to create {}: {e}", dir.display()))?;
+                clap_mangen::generate_to(command, dir)
+                    .map_err(|e| format!("failed to write man pages to {}: {e}", dir.display()))?;
+                println!("wrote man pages to {}", dir.display());
+                Ok(())
+            }
+            None => clap_mangen::Man::new(command)
+                .render(&mut std::io::stdout())
+                .map_err(|e| format!("failed to write man page: {e}")),
+        }
+    }
+}
@@ -1,7 +1,25 @@
+mod catalogue;
+mod completions;
+mod config;
 mod configure;
+mod doctor;
+mod history;
+mod man;
+mod models;
 mod path;
+mod pre_messages;
+mod profile;
 mod prompt;
 
+pub use catalogue::CmdCatalogue;
+pub use completions::CmdCompletions;
+pub use config::CmdConfig;
 pub use configure::CmdConfigure;
+pub use doctor::CmdDoctor;
+pub use history::CmdHistory;
+pub use man::CmdMan;
+pub use models::CmdModels;
 pub use path::CmdPath;
+pub use pre_messages::CmdPreMessages;
+pub use profile::CmdProfile;
 pub use prompt::CmdPrompt;
@@ -0,0 +1,99 @@
+use crate::catalogue::Catalogue;
+use crate::models::config::Config;
+use crate::traits::command_definition::CommandDefinition;
+use clap::{arg, ArgMatches, Command};
+
+pub struct CmdModels;
+
+impl CmdModels {
+    const ID_PROFILE: &'static str = "profile";
+    const ID_REFRESH: &'static str = "refresh";
+    const ID_OFFLINE: &'static str = "offline";
+    const ID_QUIET: &'static str = "quiet";
+}
+
+impl CommandDefinition for CmdModels {
+    const NAME: &'static str = "models";
+
+    fn command() -> Command {
+        Command::new(Self::NAME)
+            .about(
+                "show models available to the profile, fetched from the API and cached for a day",
+            )
+            .arg(
+                arg!(<PROFILE_NAME>)
+                    .id(Self::ID_PROFILE)
+                    .long(Self::ID_PROFILE)
+                    .short('p')
+                    .required(false)
+                    .env(crate::env::KABEUCHI_PROFILE)
+                    .default_value("default"),
+            )
+            .arg(arg!(--refresh "fetch from the API even if the cache is fresh"))
+            .arg(
+                arg!(--offline "use the cache only, even if it is old")
+                    .conflicts_with(Self::ID_REFRESH),
+            )
+            .arg(arg!(-q --quiet "show ids only (used by shell completion)"))
+    }
+
+    fn run(args: &ArgMatches) -> Result<(), String> {
+        let profile_name: &String = args.get_one(Self::ID_PROFILE).unwrap();
+        let is_refresh = args.get_flag(Self::ID_REFRESH);
+        let is_offline = args.get_flag(Self::ID_OFFLINE);
+        let is_quiet = args.get_flag(Self::ID_QUIET);
+
+        let config = Config::load()?.ok_or("not found config file")?;
+        let fetched = match crate::catalogue::load_fetched_models(profile_name)? {
+            Some(fetched) if is_offline || (!is_refresh && fetched.is_fresh()) => fetched,
+            None if is_offline => {
+                return Err(format!(
+                    "models of profile \"{profile_name}\" are not cached; run `kabeuchi models -p {profile_name}`"
+                ))
+            }
+            _ => {
+                let profile = config
+                    .get_profile(profile_name, true)?
+                    .ok_or(format!("Profile \"{profile_name}\" is not exists"))?;
+                crate::catalogue::refresh(&profile)?
+            }
+        };
+
+        if is_quiet {
+            for id in &fetched.ids {
+                println!("{id}");
+            }
+            return Ok(());
+        }
+
+        let catalogue = Catalogue::load(Some(&config))?;
+        println!(
+            "{:<40} {:>9} {:>9} {:>9}  NOTE",
+            "ID", "CONTEXT", "IN $/1M", "OUT $/1M"
+        );
+        for id in &fetched.ids {
+            let info = catalogue.find(id);
+            let format_option = |value: Option<String>| value.unwrap_or("-".to_string());
+            let note = match info {
+                Some(info) if info.is_deprecated() => "deprecated",
+                Some(info) if info.context_window.is_none() => "not in catalogue",
+                _ => "",
+            };
+            println!(
+                "{:<40} {:>9} {:>9} {:>9}  {}",
+                id,
+                format_option(info.and_then(|m| m.context_window).map(|v| v.to_string())),
+                format_option(info.and_then(|m| m.input_price).map(|v| v.to_string())),
+                format_option(info.and_then(|m| m.output_price).map(|v| v.to_string())),
+                note
+            );
+        }
+        println!(
+            "\n{} models fetched with profile \"{}\" at {}",
+            fetched.ids.len(),
+            fetched.profile,
+            fetched.format_fetched_at()
+        );
+        Ok(())
+    }
+}
@@ -5,8 +5,14 @@ use clap::{arg, ArgMatches, Command};
 pub struct CmdPath;
 struct SubCmdConfigDir;
 struct SubCmdConfigFile;
+struct SubCmdDataDir;
+struct SubCmdStateDir;
+struct SubCmdCacheDir;
 struct SubCmdPreMessagesDir;
+struct SubCmdToolsDir;
 struct SubCmdHistoryDir;
+struct SubCmdPersonasDir;
+struct SubCmdProjectFile;
 
 impl CommandDefinition for CmdPath {
     const NAME: &'static str = "path";
@@ -18,16 +24,28 @@ impl CommandDefinition for CmdPath {
             .arg_required_else_help(true)
             .subcommand(SubCmdConfigDir::command())
             .subcommand(SubCmdConfigFile::command())
+            .subcommand(SubCmdDataDir::command())
+            .subcommand(SubCmdStateDir::command())
+            .subcommand(SubCmdCacheDir::command())
             .subcommand(SubCmdPreMessagesDir::command())
+            .subcommand(SubCmdToolsDir::command())
             .subcommand(SubCmdHistoryDir::command())
+            .subcommand(SubCmdPersonasDir::command())
+            .subcommand(SubCmdProjectFile::command())
     }
 
     fn run(args: &ArgMatches) -> Result<(), String> {
         match args.subcommand() {
             Some((SubCmdConfigDir::NAME, sub_args)) => SubCmdConfigDir::run(sub_args),
             Some((SubCmdConfigFile::NAME, sub_args)) => SubCmdConfigFile::run(sub_args),
+            Some((SubCmdDataDir::NAME, sub_args)) => SubCmdDataDir::run(sub_args),
+            Some((SubCmdStateDir::NAME, sub_args)) => SubCmdStateDir::run(sub_args),
+            Some((SubCmdCacheDir::NAME, sub_args)) => SubCmdCacheDir::run(sub_args),
             Some((SubCmdPreMessagesDir::NAME, sub_args)) => SubCmdPreMessagesDir::run(sub_args),
+            Some((SubCmdToolsDir::NAME, sub_args)) => SubCmdToolsDir::run(sub_args),
             Some((SubCmdHistoryDir::NAME, sub_args)) => SubCmdHistoryDir::run(sub_args),
+            Some((SubCmdPersonasDir::NAME, sub_args)) => SubCmdPersonasDir::run(sub_args),
+            Some((SubCmdProjectFile::NAME, sub_args)) => SubCmdProjectFile::run(sub_args),
             _ => unreachable!("This is Bug."),
         }
     }
@@ -59,6 +77,45 @@ impl CommandDefinition for SubCmdConfigFile {
     }
 }
 
+impl CommandDefinition for SubCmdDataDir {
+    const NAME: &'static str = "data-dir";
+
+    fn command() -> Command {
+        Command::new(Self::NAME).about("show path of data dir (history and usage)")
+    }
+
+    fn run(_args: &ArgMatches) -> Result<(), String> {
+        println!("{}", path::get_path_data_dir()?.display());
+        Ok(())
+    }
+}
+
+impl CommandDefinition for SubCmdStateDir {
+    const NAME: &'static str = "state-dir";
+
+    fn command() -> Command {
+        Command::new(Self::NAME).about("show path of state dir (trusted projects and quarantine)")
+    }
+
+    fn run(_args: &ArgMatches) -> Result<(), String> {
+        println!("{}", path::get_path_state_dir()?.display());
+        Ok(())
+    }
+}
+
+impl CommandDefinition for SubCmdCacheDir {
+    const NAME: &'static str = "cache-dir";
+
+    fn command() -> Command {
+        Command::new(Self::NAME).about("show path of cache dir (locks and editing message)")
+    }
+
+    fn run(_args: &ArgMatches) -> Result<(), String> {
+        println!("{}", path::get_path_cache_dir()?.display());
+        Ok(())
+    }
+}
+
 impl SubCmdPreMessagesDir {
     const KEY_PROFILE_NAME: &'static str = "profile";
 }
@@ -85,6 +142,37 @@ impl CommandDefinition for SubCmdPreMessagesDir {
     }
 }
 
+impl SubCmdToolsDir {
+    const KEY_PROFILE_NAME: &'static str = "profile";
+}
+
+impl CommandDefinition for SubCmdToolsDir {
+    const NAME: &'static str = "tools-dir";
+
+    fn command() -> Command {
+        Command::new(Self::NAME)
+            .about("show path of tools dir (tool schemas for function calling)")
+            .arg(
+                arg!(<PROFILE_NAME>)
+                    .id(Self::KEY_PROFILE_NAME)
+                    .long(Self::KEY_PROFILE_NAME)
+                    .short('p')
+                    .required(false),
+            )
+    }
+
+    fn run(args: &ArgMatches) -> Result<(), String> {
+        let profile_name: Option<&String> = args.get_one(Self::KEY_PROFILE_NAME);
+        let mut directory = path::get_path_tools_dir()?;
+        if let Some(profile_name) = profile_name {
+            directory = directory.join(profile_name);
+        }
+        println!("{}", directory.display());
+
+        Ok(())
+    }
+}
+
 impl SubCmdHistoryDir {
     const KEY_PROFILE_NAME: &'static str = "profile";
 }
@@ -115,3 +203,52 @@ impl CommandDefinition for SubCmdHistoryDir {
         Ok(())
     }
 }
+
+impl SubCmdPersonasDir {
+    const KEY_PERSONA_NAME: &'static str = "persona";
+}
+
+impl CommandDefinition for SubCmdPersonasDir {
+    const NAME: &'static str = "personas-dir";
+
+    fn command() -> Command {
+        Command::new(Self::NAME)
+            .about("show path of personas dir")
+            .arg(
+                arg!(<PERSONA_NAME>)
+                    .id(Self::KEY_PERSONA_NAME)
+                    .long(Self::KEY_PERSONA_NAME)
+                    .required(false),
+            )
+    }
+
+    fn run(args: &ArgMatches) -> Result<(), String> {
+        let persona_name: Option<&String> = args.get_one(Self::KEY_PERSONA_NAME);
+        let directory = if let Some(persona_name) = persona_name {
+            path::get_path_persona_dir(persona_name)?
+        } else {
+            path::get_path_personas_dir()?
+        };
+        println!("{}", directory.display());
+
+        Ok(())
+    }
+}
+
+impl CommandDefinition for SubCmdProjectFile {
+    const NAME: &'static str = "project-file";
+
+    fn command() -> Command {
+        Command::new(Self::NAME)
+            .about("show path of the project config (.kabeuchi.toml) found from current directory")
+    }
+
+    fn run(_args: &ArgMatches) -> Result<(), String> {
+        let path = crate::project::find_project_config_file()?.ok_or(format!(
+            "not found {} in current directory and its parents",
+            crate::project::PROJECT_CONFIG_FILE
+        ))?;
+        println!("{}", path.display());
+        Ok(())
+    }
+}
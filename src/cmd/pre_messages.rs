@@ -0,0 +1,439 @@
+use crate::cmd::CmdPrompt;
+use crate::models::config::Config;
+use crate::models::messages::{RawSavedMessage, SavedMessage};
+use crate::path::{get_files_in_dir, get_path_config_dir, get_path_profile_pre_messages_dir};
+use crate::traits::command_definition::CommandDefinition;
+use clap::{arg, builder::PossibleValuesParser, ArgAction, ArgMatches, Command};
+use std::path::{Path, PathBuf};
+
+pub struct CmdPreMessages;
+struct SubCmdList;
+struct SubCmdShow;
+struct SubCmdAdd;
+struct SubCmdEdit;
+struct SubCmdRm;
+struct SubCmdReorder;
+struct SubCmdValidate;
+
+const ID_PROFILE: &str = "profile";
+const ID_FILE: &str = "file";
+
+fn arg_profile() -> clap::Arg {
+    arg!(<PROFILE_NAME>)
+        .id(ID_PROFILE)
+        .long(ID_PROFILE)
+        .short('p')
+        .required(false)
+        .env(crate::env::KABEUCHI_PROFILE)
+        .default_value("default")
+}
+
+fn arg_file() -> clap::Arg {
+    arg!(<FILE>)
+        .id(ID_FILE)
+        .help("file name or number shown by `pre-messages list`")
+}
+
+impl CommandDefinition for CmdPreMessages {
+    const NAME: &'static str = "pre-messages";
+
+    fn command() -> Command {
+        Command::new(Self::NAME)
+            .about("manage pre messages of profile")
+            .subcommand_required(true)
+            .arg_required_else_help(true)
+            .subcommand(SubCmdList::command())
+            .subcommand(SubCmdShow::command())
+            .subcommand(SubCmdAdd::command())
+            .subcommand(SubCmdEdit::command())
+            .subcommand(SubCmdRm::command())
+            .subcommand(SubCmdReorder::command())
+            .subcommand(SubCmdValidate::command())
+    }
+
+    fn run(args: &ArgMatches) -> Result<(), String> {
+        match args.subcommand() {
+            Some((SubCmdList::NAME, sub_args)) => SubCmdList::run(sub_args),
+            Some((SubCmdShow::NAME, sub_args)) => SubCmdShow::run(sub_args),
+            Some((SubCmdAdd::NAME, sub_args)) => SubCmdAdd::run(sub_args),
+            Some((SubCmdEdit::NAME, sub_args)) => SubCmdEdit::run(sub_args),
+            Some((SubCmdRm::NAME, sub_args)) => SubCmdRm::run(sub_args),
+            Some((SubCmdReorder::NAME, sub_args)) => SubCmdReorder::run(sub_args),
+            Some((SubCmdValidate::NAME, sub_args)) => SubCmdValidate::run(sub_args),
+            _ => unreachable!("This is Bug."),
+        }
+    }
+}
+
+fn get_file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+fn get_role(message: &SavedMessage) -> &'static str {
+    match message {
+        SavedMessage::System(_) => "system",
+        SavedMessage::Assistant(_) => "assistant",
+        SavedMessage::User(_) => "user",
+    }
+}
+
+fn get_text(message: &SavedMessage) -> &str {
+    match message {
+        SavedMessage::System(text) => text,
+        SavedMessage::Assistant(text) => text,
+        SavedMessage::User(text) => text,
+    }
+}
+
+/// Find a file of the profile by 1-based number or file name.
+fn find_file(profile_name: &str, file: &str) -> Result<PathBuf, String> {
+    let files = get_files_in_dir(&get_path_profile_pre_messages_dir(profile_name)?)?;
+    let found = match file.parse::<usize>() {
+        Ok(number) if number >= 1 && number <= files.len() => files.get(number - 1).cloned(),
+        _ => files.into_iter().find(|p| get_file_name(p) == file),
+    };
+    found.ok_or(format!(
+        "pre message file \"{file}\" is not exists (profile: {profile_name})"
+    ))
+}
+
+/// Split the leading number of a file name, e.g. "002_system.txt" -> (Some(2), "system.txt").
+fn split_number(file_name: &str) -> (Option<usize>, &str) {
+    let digits = file_name.chars().take_while(|c| c.is_ascii_digit()).count();
+    if digits == 0 {
+        return (None, file_name);
+    }
+    let rest = &file_name[digits..];
+    let rest = rest
+        .strip_prefix('_')
+        .or_else(|| rest.strip_prefix('-'))
+        .unwrap_or(rest);
+    (file_name[..digits].parse().ok(), rest)
+}
+
+fn numbered_file_name(number: usize, rest: &str) -> String {
+    if rest.is_empty() || rest.starts_with('.') {
+        format!("{number:03}{rest}")
+    } else {
+        format!("{number:03}_{rest}")
+    }
+}
+
+impl CommandDefinition for SubCmdList {
+    const NAME: &'static str = "list";
+
+    fn command() -> Command {
+        Command::new(Self::NAME)
+            .about("show pre message files of profile")
+            .arg(arg_profile())
+    }
+
+    fn run(args: &ArgMatches) -> Result<(), String> {
+        let profile_name: &String = args.get_one(ID_PROFILE).unwrap();
+        let files = get_files_in_dir(&get_path_profile_pre_messages_dir(profile_name)?)?;
+        let root = get_path_config_dir()?;
+
+        for (i, path) in files.iter().enumerate() {
+            let summary = match crate::pre_messages::load_pre_message_file(path, &root) {
+                Ok(file) => {
+                    let roles: Vec<String> = file
+                        .messages
+                        .iter()
+                        .map(|m| match m.condition.describe() {
+                            Some(condition) => format!("{} ({})", get_role(&m.message), condition),
+                            None => get_role(&m.message).to_string(),
+                        })
+                        .collect();
+                    let order = if file.order != 0 {
+                        format!(" order={}", file.order)
+                    } else {
+                        String::new()
+                    };
+                    format!(
+                        "{} messages [{}]{}",
+                        file.messages.len(),
+                        roles.join(", "),
+                        order
+                    )
+                }
+                Err(e) => format!("invalid: {e}"),
+            };
+            println!("{:>3}  {}  {}", i + 1, get_file_name(path), summary);
+        }
+
+        Ok(())
+    }
+}
+
+impl CommandDefinition for SubCmdShow {
+    const NAME: &'static str = "show";
+
+    fn command() -> Command {
+        Command::new(Self::NAME)
+            .about("show pre messages as they are sent by `prompt`")
+            .arg(arg_profile())
+            .args(CmdPrompt::pre_message_args())
+    }
+
+    fn run(args: &ArgMatches) -> Result<(), String> {
+        let profile_name: &String = args.get_one(ID_PROFILE).unwrap();
+        let options = CmdPrompt::get_pre_message_options(args);
+
+        let config = Config::load_with_project()?.ok_or("not found config file")?;
+        let profile = config
+            .get_profile(profile_name, true)?
+            .ok_or(format!("Profile \"{profile_name}\" is not exists"))?;
+
+        for (i, message) in crate::pre_messages::get_pre_messages(&profile, &options)?
+            .iter()
+            .enumerate()
+        {
+            println!("--- {} ({})", i + 1, get_role(message));
+            println!("{}\n", get_text(message));
+        }
+
+        Ok(())
+    }
+}
+
+impl SubCmdAdd {
+    const ID_ROLE: &'static str = "role";
+    const ID_MESSAGE: &'static str = "message";
+    const ID_FORMAT: &'static str = "format";
+}
+
+impl CommandDefinition for SubCmdAdd {
+    const NAME: &'static str = "add";
+
+    fn command() -> Command {
+        Command::new(Self::NAME)
+            .about("add pre message as new file (opens $EDITOR when --message is omitted)")
+            .arg(arg_profile())
+            .arg(
+                arg!(<ROLE>)
+                    .id(Self::ID_ROLE)
+                    .long(Self::ID_ROLE)
+                    .short('r')
+                    .required(false)
+                    .default_value("system")
+                    .value_parser(PossibleValuesParser::new(["system", "assistant", "user"])),
+            )
+            .arg(
+                arg!(<MESSAGE>)
+                    .id(Self::ID_MESSAGE)
+                    .long(Self::ID_MESSAGE)
+                    .short('m')
+                    .required(false),
+            )
+            .arg(
+                arg!(<FORMAT>)
+                    .id(Self::ID_FORMAT)
+                    .long(Self::ID_FORMAT)
+                    .required(false)
+                    .default_value("yaml")
+                    .value_parser(PossibleValuesParser::new(["yaml", "json", "md", "text"])),
+            )
+    }
+
+    fn run(args: &ArgMatches) -> Result<(), String> {
+        let profile_name: &String = args.get_one(ID_PROFILE).unwrap();
+        let role: &String = args.get_one(Self::ID_ROLE).unwrap();
+        let message: Option<&String> = args.get_one(Self::ID_MESSAGE);
+        let format: &String = args.get_one(Self::ID_FORMAT).unwrap();
+
+        let text = if let Some(message) = message {
+            message.clone()
+        } else {
+            let path = crate::path::get_path_editting_message_file()?;
+            crate::fs::save_text(&path, "")?;
+            crate::input::open_editor(&path)?;
+            let text = crate::fs::load_text(&path)?;
+            if text.trim().is_empty() {
+                return Err("Aborting due to empty message".to_string());
+            }
+            text.trim_end().to_string()
+        };
+
+        let message = match role.as_str() {
+            "assistant" => SavedMessage::Assistant(text),
+            "user" => SavedMessage::User(text),
+            _ => SavedMessage::System(text),
+        };
+
+        let directory = get_path_profile_pre_messages_dir(profile_name)?;
+        let number = get_files_in_dir(&directory)?
+            .iter()
+            .filter_map(|p| split_number(&get_file_name(p)).0)
+            .max()
+            .unwrap_or(0)
+            + 1;
+
+        let (file_name, content) = match format.as_str() {
+            "json" => (
+                numbered_file_name(number, ".json"),
+                serde_json::to_string_pretty(&vec![message])
+                    .map_err(|e| format!("failed to serialize json: {e}"))?,
+            ),
+            "md" => (
+                numbered_file_name(number, ".md"),
+                format!("---\nrole: {role}\n---\n\n{}\n", get_text(&message)),
+            ),
+            "text" => (
+                numbered_file_name(number, &format!("{role}.txt")),
+                get_text(&message).to_string(),
+            ),
+            _ => {
+                let raw: Vec<RawSavedMessage> = vec![message.into()];
+                (
+                    numbered_file_name(number, ".yaml"),
+                    serde_yaml::to_string(&raw)
+                        .map_err(|e| format!("failed to serialize yaml: {e}"))?,
+                )
+            }
+        };
+
+        let path = directory.join(file_name);
+        crate::fs::save_text(&path, &content)?;
+        println!("{}", path.display());
+
+        Ok(())
+    }
+}
+
+impl CommandDefinition for SubCmdEdit {
+    const NAME: &'static str = "edit";
+
+    fn command() -> Command {
+        Command::new(Self::NAME)
+            .about("edit pre message file with $EDITOR")
+            .arg(arg_profile())
+            .arg(arg_file())
+    }
+
+    fn run(args: &ArgMatches) -> Result<(), String> {
+        let profile_name: &String = args.get_one(ID_PROFILE).unwrap();
+        let file: &String = args.get_one(ID_FILE).unwrap();
+
+        let path = find_file(profile_name, file)?;
+        crate::input::open_editor(&path)?;
+
+        if let Err(e) = crate::pre_messages::load_pre_message_file(&path, &get_path_config_dir()?) {
+            println!("warning: {e}");
+        }
+        Ok(())
+    }
+}
+
+impl CommandDefinition for SubCmdRm {
+    const NAME: &'static str = "rm";
+
+    fn command() -> Command {
+        Command::new(Self::NAME)
+            .about("remove pre message file")
+            .arg(arg_profile())
+            .arg(arg_file())
+    }
+
+    fn run(args: &ArgMatches) -> Result<(), String> {
+        let profile_name: &String = args.get_one(ID_PROFILE).unwrap();
+        let file: &String = args.get_one(ID_FILE).unwrap();
+
+        let path = find_file(profile_name, file)?;
+        std::fs::remove_file(&path)
+            .map_err(|e| format!("failed to remove file: path={}, err={}", path.display(), e))?;
+        println!("removed {}", path.display());
+        Ok(())
+    }
+}
+
+impl SubCmdReorder {
+    const ID_FILES: &'static str = "files";
+}
+
+impl CommandDefinition for SubCmdReorder {
+    const NAME: &'static str = "reorder";
+
+    fn command() -> Command {
+        Command::new(Self::NAME)
+            .about("renumber pre message files; the given files come first in the given order")
+            .arg(arg_profile())
+            .arg(
+                arg!(<FILES>)
+                    .id(Self::ID_FILES)
+                    .action(ArgAction::Append)
+                    .help("file names or numbers shown by `pre-messages list`"),
+            )
+    }
+
+    fn run(args: &ArgMatches) -> Result<(), String> {
+        let profile_name: &String = args.get_one(ID_PROFILE).unwrap();
+        let files: Vec<&String> = args.get_many(Self::ID_FILES).unwrap().collect();
+
+        let directory = get_path_profile_pre_messages_dir(profile_name)?;
+        let mut order = Vec::new();
+        for file in files {
+            let path = find_file(profile_name, file)?;
+            if order.contains(&path) {
+                return Err(format!("file \"{file}\" is given twice"));
+            }
+            order.push(path);
+        }
+        for path in get_files_in_dir(&directory)? {
+            if !order.contains(&path) {
+                order.push(path);
+            }
+        }
+
+        // rename through temporary names so that new names never collide with old ones
+        let mut renames = Vec::new();
+        for (i, path) in order.iter().enumerate() {
+            let file_name = get_file_name(path);
+            let new_name = numbered_file_name(i + 1, split_number(&file_name).1);
+            let path_tmp = directory.join(format!(".reorder.{i}.tmp"));
+            std::fs::rename(path, &path_tmp).map_err(|e| {
+                format!("failed to rename file: path={}, err={}", path.display(), e)
+            })?;
+            renames.push((path_tmp, directory.join(new_name)));
+        }
+        for (path_tmp, path) in renames {
+            std::fs::rename(&path_tmp, &path).map_err(|e| {
+                format!("failed to rename file: path={}, err={}", path.display(), e)
+            })?;
+            println!("{}", get_file_name(&path));
+        }
+
+        Ok(())
+    }
+}
+
+impl CommandDefinition for SubCmdValidate {
+    const NAME: &'static str = "validate";
+
+    fn command() -> Command {
+        Command::new(Self::NAME)
+            .about("check that every pre message file of profile can be loaded")
+            .arg(arg_profile())
+    }
+
+    fn run(args: &ArgMatches) -> Result<(), String> {
+        let profile_name: &String = args.get_one(ID_PROFILE).unwrap();
+        let files = get_files_in_dir(&get_path_profile_pre_messages_dir(profile_name)?)?;
+
+        let root = get_path_config_dir()?;
+        let mut count = 0;
+        for path in &files {
+            if let Err(e) = crate::pre_messages::load_pre_message_file(path, &root) {
+                println!("{e}");
+                count += 1;
+            }
+        }
+
+        if count > 0 {
+            return Err(format!("{count} invalid pre message files"));
+        }
+        println!("{} files are valid", files.len());
+        Ok(())
+    }
+}
@@ -0,0 +1,380 @@
+use crate::input::get_confirm;
+use crate::models::config::{validate_profile_name, Config};
+use crate::models::profile_field::{mask_secret, PROFILE_FIELDS};
+use crate::path::{get_path_profile_pre_messages_dir, get_path_profile_tools_dir};
+use crate::traits::command_definition::CommandDefinition;
+use clap::{arg, ArgMatches, Command};
+use std::path::PathBuf;
+
+pub struct CmdProfile;
+struct SubCmdList;
+struct SubCmdShow;
+struct SubCmdCopy;
+struct SubCmdRename;
+struct SubCmdDelete;
+
+const ID_NAME: &str = "name";
+const ID_FROM: &str = "from";
+const ID_TO: &str = "to";
+
+impl CommandDefinition for CmdProfile {
+    const NAME: &'static str = "profile";
+
+    fn command() -> Command {
+        Command::new(Self::NAME)
+            .about("manage profiles")
+            .subcommand_required(true)
+            .arg_required_else_help(true)
+            .subcommand(SubCmdList::command())
+            .subcommand(SubCmdShow::command())
+            .subcommand(SubCmdCopy::command())
+            .subcommand(SubCmdRename::command())
+            .subcommand(SubCmdDelete::command())
+    }
+
+    fn run(args: &ArgMatches) -> Result<(), String> {
+        match args.subcommand() {
+            Some((SubCmdList::NAME, sub_args)) => SubCmdList::run(sub_args),
+            Some((SubCmdShow::NAME, sub_args)) => SubCmdShow::run(sub_args),
+            Some((SubCmdCopy::NAME, sub_args)) => SubCmdCopy::run(sub_args),
+            Some((SubCmdRename::NAME, sub_args)) => SubCmdRename::run(sub_args),
+            Some((SubCmdDelete::NAME, sub_args)) => SubCmdDelete::run(sub_args),
+            _ => unreachable!("This is Bug."),
+        }
+    }
+}
+
+fn load_config() -> Result<Config, String> {
+    Config::load()?.ok_or("not found config file".to_string())
+}
+
+fn ensure_exists(config: &Config, name: &str) -> Result<(), String> {
+    if config.profile.iter().any(|p| p.name == name) {
+        Ok(())
+    } else {
+        Err(format!("Profile \"{name}\" is not exists"))
+    }
+}
+
+fn ensure_not_exists(config: &Config, name: &str) -> Result<(), String> {
+    if config.profile.iter().any(|p| p.name == name) {
+        Err(format!("Profile \"{name}\" already exists"))
+    } else {
+        Ok(())
+    }
+}
+
+/// Directories of a profile which are copied, moved and removed with it.
+fn get_profile_dirs(name: &str) -> Result<Vec<PathBuf>, String> {
+    Ok(vec![
+        get_path_profile_pre_messages_dir(name)?,
+        get_path_profile_tools_dir(name)?,
+    ])
+}
+
+/// Pairs of directories of `from` and `to`, failing when a directory of `from` would
+/// overwrite an existing one of `to`.
+fn get_profile_dir_pairs(from: &str, to: &str) -> Result<Vec<(PathBuf, PathBuf)>, String> {
+    let pairs: Vec<(PathBuf, PathBuf)> = get_profile_dirs(from)?
+        .into_iter()
+        .zip(get_profile_dirs(to)?)
+        .collect();
+    for (dir_from, dir_to) in &pairs {
+        if dir_from.is_dir() && dir_to.exists() {
+            return Err(format!(
+                "directory already exists: path={}",
+                dir_to.display()
+            ));
+        }
+    }
+    Ok(pairs)
+}
+
+fn remove_dir(dir: &std::path::Path) -> Result<(), String> {
+    if !dir.is_dir() {
+        return Ok(());
+    }
+    std::fs::remove_dir_all(dir).map_err(|e| {
+        format!(
+            "failed to remove directory: path={}, err={}",
+            dir.display(),
+            e
+        )
+    })
+}
+
+fn move_dir(from: &std::path::Path, to: &std::path::Path) -> Result<(), String> {
+    if !from.is_dir() {
+        return Ok(());
+    }
+    if to.exists() {
+        return Err(format!("directory already exists: path={}", to.display()));
+    }
+    std::fs::rename(from, to).map_err(|e| {
+        format!(
+            "failed to move directory: from={}, to={}, err={}",
+            from.display(),
+            to.display(),
+            e
+        )
+    })
+}
+
+impl CommandDefinition for SubCmdList {
+    const NAME: &'static str = "list";
+
+    fn command() -> Command {
+        Command::new(Self::NAME).about("show names of profiles")
+    }
+
+    fn run(_args: &ArgMatches) -> Result<(), String> {
+        for profile in &load_config()?.profile {
+            match &profile.source_profile {
+                Some(source_profile) => println!("{} (source: {})", profile.name, source_profile),
+                None => println!("{}", profile.name),
+            }
+        }
+        Ok(())
+    }
+}
+
+impl SubCmdShow {
+    const ID_RESOLVED: &'static str = "resolved";
+    const ID_SHOW_SECRET: &'static str = "show-secret";
+}
+
+impl CommandDefinition for SubCmdShow {
+    const NAME: &'static str = "show";
+
+    fn command() -> Command {
+        Command::new(Self::NAME)
+            .about("show profile as TOML")
+            .arg(arg!(<PROFILE_NAME>).id(ID_NAME))
+            .arg(
+                arg!(--resolved "show the effective profile after following source_profile, project config and environment variables"),
+            )
+            .arg(arg!(--"show-secret" "show api_key and organization_id without masking"))
+    }
+
+    fn run(args: &ArgMatches) -> Result<(), String> {
+        let name: &String = args.get_one(ID_NAME).unwrap();
+        let is_resolved = args.get_flag(Self::ID_RESOLVED);
+        let is_show_secret = args.get_flag(Self::ID_SHOW_SECRET);
+
+        let config = if is_resolved {
+            Config::load_with_project()?.ok_or("not found config file")?
+        } else {
+            load_config()?
+        };
+        let mut profile = config
+            .get_profile(name, is_resolved)?
+            .ok_or(format!("Profile \"{name}\" is not exists"))?;
+
+        if !is_show_secret {
+            for field in PROFILE_FIELDS.iter().filter(|f| f.is_secret) {
+                if let Some(value) = profile.get_value(field.key)? {
+                    profile.set_value(field.key, &mask_secret(&value))?;
+                }
+            }
+        }
+
+        if is_resolved && !profile.resolved_sources.is_empty() {
+            println!("# resolved from: {}", profile.resolved_sources.join(" -> "));
+        }
+        if let Some(project) = config.project.as_ref().filter(|_| is_resolved) {
+            println!("# project: {}", project.get_path_config_file().display());
+        }
+        print!(
+            "{}",
+            toml::to_string_pretty(&profile)
+                .map_err(|e| format!("failed to serialize profile: {e}"))?
+        );
+        Ok(())
+    }
+}
+
+impl CommandDefinition for SubCmdCopy {
+    const NAME: &'static str = "copy";
+
+    fn command() -> Command {
+        Command::new(Self::NAME)
+            .about("copy profile with its pre messages and tools")
+            .arg(arg!(<FROM>).id(ID_FROM))
+            .arg(arg!(<TO>).id(ID_TO))
+    }
+
+    fn run(args: &ArgMatches) -> Result<(), String> {
+        let from: &String = args.get_one(ID_FROM).unwrap();
+        let to: &String = args.get_one(ID_TO).unwrap();
+
+        validate_profile_name(to)?;
+        let config = load_config()?;
+        ensure_exists(&config, from)?;
+        ensure_not_exists(&config, to)?;
+
+        let pairs = get_profile_dir_pairs(from, to)?;
+        let mut copied = Vec::new();
+        let mut result = Ok(());
+        for (dir_from, dir_to) in &pairs {
+            if dir_from.is_dir() {
+                // a partial copy is removed as well
+                copied.push(dir_to);
+                result = crate::fs::copy_dir_all(dir_from, dir_to);
+                if result.is_err() {
+                    break;
+                }
+            }
+        }
+        let result = result.and_then(|_| {
+            Config::update(|config| {
+                if let Some(profile) = config.profile.iter().find(|p| &p.name == from) {
+                    let mut profile = profile.clone();
+                    profile.name = to.clone();
+                    config.upsert_profile(profile);
+                }
+            })
+        });
+        if let Err(e) = result {
+            let rollback: Vec<String> = copied
+                .into_iter()
+                .filter_map(|dir| remove_dir(dir).err())
+                .collect();
+            return Err(if rollback.is_empty() {
+                e
+            } else {
+                format!(
+                    "{e}; failed to remove copied directories: {}",
+                    rollback.join(", ")
+                )
+            });
+        }
+        Ok(())
+    }
+}
+
+impl CommandDefinition for SubCmdRename {
+    const NAME: &'static str = "rename";
+
+    fn command() -> Command {
+        Command::new(Self::NAME)
+            .about("rename profile with its pre messages, tools and history")
+            .arg(arg!(<FROM>).id(ID_FROM))
+            .arg(arg!(<TO>).id(ID_TO))
+    }
+
+    fn run(args: &ArgMatches) -> Result<(), String> {
+        let from: &String = args.get_one(ID_FROM).unwrap();
+        let to: &String = args.get_one(ID_TO).unwrap();
+
+        validate_profile_name(to)?;
+        let config = load_config()?;
+        ensure_exists(&config, from)?;
+        ensure_not_exists(&config, to)?;
+
+        let _lock = crate::fs::lock_exclusive(&crate::path::get_path_profile_lock_file(from)?)?;
+        let store = crate::store::open_configured_store(Some(&config))?;
+
+        // check every target before moving anything, so that a failure leaves the profile as it was
+        let pairs = get_profile_dir_pairs(from, to)?;
+        if store.list_profiles()?.contains(to) {
+            return Err(format!("history of profile \"{to}\" already exists"));
+        }
+
+        let mut moved = Vec::new();
+        let mut result = Ok(());
+        for (dir_from, dir_to) in &pairs {
+            result = move_dir(dir_from, dir_to);
+            if result.is_err() {
+                break;
+            }
+            moved.push((dir_from, dir_to));
+        }
+        let result = result
+            .and_then(|_| store.rename_profile(from, to))
+            .and_then(|_| {
+                Config::update(|config| {
+                    for profile in config.profile.iter_mut() {
+                        if &profile.name == from {
+                            profile.name = to.clone();
+                        }
+                        if profile.source_profile.as_ref() == Some(from) {
+                            profile.source_profile = Some(to.clone());
+                            println!("updated source_profile of \"{}\"", profile.name);
+                        }
+                    }
+                })
+                .map_err(|e| match store.rename_profile(to, from) {
+                    Ok(()) => e,
+                    Err(rollback) => format!("{e}; failed to move history back: {rollback}"),
+                })
+            });
+        if let Err(e) = result {
+            let rollback: Vec<String> = moved
+                .into_iter()
+                .rev()
+                .filter_map(|(dir_from, dir_to)| move_dir(dir_to, dir_from).err())
+                .collect();
+            return Err(if rollback.is_empty() {
+                e
+            } else {
+                format!(
+                    "{e}; failed to move directories back: {}",
+                    rollback.join(", ")
+                )
+            });
+        }
+        Ok(())
+    }
+}
+
+impl SubCmdDelete {
+    const ID_YES: &'static str = "yes";
+}
+
+impl CommandDefinition for SubCmdDelete {
+    const NAME: &'static str = "delete";
+
+    fn command() -> Command {
+        Command::new(Self::NAME)
+            .about("delete profile with its pre messages, tools and history")
+            .arg(arg!(<PROFILE_NAME>).id(ID_NAME))
+            .arg(arg!(-y --yes "delete without confirmation"))
+    }
+
+    fn run(args: &ArgMatches) -> Result<(), String> {
+        let name: &String = args.get_one(ID_NAME).unwrap();
+        let is_yes = args.get_flag(Self::ID_YES);
+
+        let config = load_config()?;
+        ensure_exists(&config, name)?;
+
+        // values inherited by dependents would silently disappear
+        let dependents: Vec<&str> = config
+            .profile
+            .iter()
+            .filter(|p| p.source_profile.as_ref() == Some(name))
+            .map(|p| p.name.as_str())
+            .collect();
+        if !dependents.is_empty() {
+            return Err(format!(
+                "Profile \"{name}\" is source_profile of {}; change their source_profile first",
+                dependents.join(", ")
+            ));
+        }
+        if !is_yes {
+            println!("delete profile \"{name}\" with its pre messages, tools and history.");
+            if !get_confirm("continue? [y/N]: ", false)?.unwrap_or(false) {
+                return Err("Aborting delete".to_string());
+            }
+        }
+
+        let _lock = crate::fs::lock_exclusive(&crate::path::get_path_profile_lock_file(name)?)?;
+        for dir in get_profile_dirs(name)? {
+            remove_dir(&dir)?;
+        }
+        crate::store::open_configured_store(Some(&config))?.delete_profile(name)?;
+
+        Config::update(|config| config.profile.retain(|p| &p.name != name))?;
+        Ok(())
+    }
+}
@@ -1,12 +1,66 @@
+use crate::catalogue::Catalogue;
 use crate::models::config::Config;
+use crate::pre_messages::PreMessageOptions;
 use crate::traits::command_definition::CommandDefinition;
-use clap::{arg, ArgMatches, Command};
+use clap::{arg, Arg, ArgAction, ArgMatches, Command};
 
 pub struct CmdPrompt;
 
 impl CmdPrompt {
     const ID_PROFILE: &'static str = "profile";
     const ID_MESSAGE: &'static str = "message";
+    const ID_SESSION: &'static str = "session";
+    const ID_MODEL: &'static str = "model";
+    const ID_TOOL_CHOICE: &'static str = "tool-choice";
+    const ID_VAR: &'static str = "var";
+    const ID_PERSONA: &'static str = "persona";
+    const ID_TAG: &'static str = "tag";
+
+    /// arguments which select and fill pre messages, shared with `pre-messages show`
+    pub fn pre_message_args() -> [Arg; 3] {
+        [
+            arg!(<KEY_VALUE>)
+                .id(Self::ID_VAR)
+                .long(Self::ID_VAR)
+                .required(false)
+                .action(ArgAction::Append)
+                .value_parser(crate::template::parse_var)
+                .help("template variable of pre messages (KEY=VALUE)"),
+            arg!(<PERSONA>)
+                .id(Self::ID_PERSONA)
+                .long(Self::ID_PERSONA)
+                .required(false)
+                .action(ArgAction::Append)
+                .value_delimiter(',')
+                .help(
+                    "persona sets to append to pre messages, in order (overrides default_persona)",
+                ),
+            arg!(<TAG>)
+                .id(Self::ID_TAG)
+                .long(Self::ID_TAG)
+                .required(false)
+                .action(ArgAction::Append)
+                .value_delimiter(',')
+                .help("send pre messages whose `tags` condition contains the tag"),
+        ]
+    }
+
+    pub fn get_pre_message_options(args: &ArgMatches) -> PreMessageOptions {
+        PreMessageOptions {
+            vars: args
+                .get_many::<(String, String)>(Self::ID_VAR)
+                .map(|vars| vars.cloned().collect())
+                .unwrap_or_default(),
+            personas: args
+                .get_many::<String>(Self::ID_PERSONA)
+                .map(|personas| personas.cloned().collect())
+                .unwrap_or_default(),
+            tags: args
+                .get_many::<String>(Self::ID_TAG)
+                .map(|tags| tags.cloned().collect())
+                .unwrap_or_default(),
+        }
+    }
 }
 
 impl CommandDefinition for CmdPrompt {
@@ -21,6 +75,7 @@ impl CommandDefinition for CmdPrompt {
                     .long(Self::ID_PROFILE)
                     .short('p')
                     .required(false)
+                    .env(crate::env::KABEUCHI_PROFILE)
                     .default_value("default"),
             )
             .arg(
@@ -30,16 +85,46 @@ impl CommandDefinition for CmdPrompt {
                     .short('m')
                     .required(false),
             )
+            .arg(
+                arg!(<SESSION>)
+                    .id(Self::ID_SESSION)
+                    .long(Self::ID_SESSION)
+                    .short('s')
+                    .required(false)
+                    .help("continue the history of a session created by `history fork`"),
+            )
+            .arg(
+                arg!(<MODEL>)
+                    .id(Self::ID_MODEL)
+                    .long(Self::ID_MODEL)
+                    .required(false)
+                    .help("model used instead of the one of profile (see `kabeuchi models`)"),
+            )
+            .arg(
+                arg!(<CHOICE>)
+                    .id(Self::ID_TOOL_CHOICE)
+                    .long(Self::ID_TOOL_CHOICE)
+                    .required(false)
+                    .help("none, auto, required or a tool name to force (default: auto when the profile has tools)"),
+            )
+            .args(Self::pre_message_args())
     }
 
     fn run(args: &ArgMatches) -> Result<(), String> {
         let profile_name: &String = args.get_one(Self::ID_PROFILE).unwrap();
         let message: Option<&String> = args.get_one(Self::ID_MESSAGE);
+        let session_name: Option<&String> = args.get_one(Self::ID_SESSION);
+        let model: Option<&String> = args.get_one(Self::ID_MODEL);
+        let tool_choice: Option<&String> = args.get_one(Self::ID_TOOL_CHOICE);
+        let pre_message_options = Self::get_pre_message_options(args);
 
-        let config = Config::load()?.ok_or("not found config file")?;
-        let profile = config
+        let config = Config::load_with_project()?.ok_or("not found config file")?;
+        let mut profile = config
             .get_profile(profile_name, true)?
             .ok_or(format!("Profile \"{profile_name}\" is not exists"))?;
+        if let Some(model) = model {
+            profile.model = Some(model.clone());
+        }
 
         let message = if let Some(message) = message {
             message.clone()
@@ -47,7 +132,28 @@ impl CommandDefinition for CmdPrompt {
             get_message_from_editor()?
         };
 
-        crate::api::call_chat_completion(&profile, &message)?;
+        // serialize runs on the same profile so that each one sees the history saved by the other
+        let _lock = if profile.get_use_history() {
+            Some(crate::fs::lock_exclusive(
+                &crate::path::get_path_profile_lock_file(&profile.name)?,
+            )?)
+        } else {
+            None
+        };
+
+        let catalogue = Catalogue::load(Some(&config))?;
+        catalogue.warn_profile(&profile);
+
+        let store = crate::store::open_configured_store(Some(&config))?;
+        crate::api::call_chat_completion(
+            store.as_ref(),
+            &catalogue,
+            &profile,
+            session_name.map(|s| s.as_str()),
+            &pre_message_options,
+            tool_choice.map(|s| s.as_str()),
+            &message,
+        )?;
 
         Ok(())
     }
@@ -56,17 +162,8 @@ impl CommandDefinition for CmdPrompt {
 fn get_message_from_editor() -> Result<String, String> {
     let path = crate::path::get_path_editting_message_file()?;
 
-    let editor = std::env::var("EDITOR")
-        .map_err(|e| format!("failed to get environment variable \"EDITOR\": {e}"))?;
-
     crate::fs::save_text(&path, "")?;
-
-    std::process::Command::new(editor)
-        .arg(&path)
-        .spawn()
-        .map_err(|e| format!("failed to spawn editor: {e}"))?
-        .wait()
-        .map_err(|e| format!("failed to edit message: {e}"))?;
+    crate::input::open_editor(&path)?;
 
     let text = crate::fs::load_text(&path)?;
 
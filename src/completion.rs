@@ -0,0 +1,105 @@
+use crate::catalogue::Catalogue;
+use crate::models::config::Config;
+use clap::Command;
+use clap_complete::engine::{ArgValueCandidates, CompletionCandidate};
+
+/// environment variable with which the shell asks kabeuchi for candidates
+pub const COMPLETE_VAR: &str = "KABEUCHI_COMPLETE";
+
+/// shells supported by `kabeuchi completions`
+pub const SHELLS: [&str; 5] = ["bash", "zsh", "fish", "powershell", "elvish"];
+
+/// Answer the completion request of the shell and exit, if this is one.
+pub fn complete(factory: fn() -> Command) {
+    clap_complete::CompleteEnv::with_factory(factory)
+        .var(COMPLETE_VAR)
+        .complete();
+}
+
+/// Script which registers the completion of kabeuchi to `shell`.
+pub fn write_registration(
+    shell: &str,
+    bin: &str,
+    out: &mut dyn std::io::Write,
+) -> Result<(), String> {
+    let shells = clap_complete::env::Shells::builtins();
+    let completer = shells
+        .completer(shell)
+        .ok_or(format!("unknown shell \"{shell}\""))?;
+    completer
+        .write_registration(COMPLETE_VAR, bin, bin, bin, out)
+        .map_err(|e| format!("failed to write completion script: {e}"))
+}
+
+/// Attach dynamic candidates to args of the whole tree by their value names,
+/// so that every `<PROFILE_NAME>`, `<SESSION>` and `<MODEL>` is completed the same way.
+pub fn add_candidates(command: Command) -> Command {
+    let command = command.mut_args(|arg| {
+        let value_name = arg
+            .get_value_names()
+            .and_then(|names| names.first())
+            .map(|name| name.to_string());
+        match value_name.as_deref() {
+            Some("PROFILE_NAME") => arg.add(ArgValueCandidates::new(profile_candidates)),
+            Some("SESSION") => arg.add(ArgValueCandidates::new(session_candidates)),
+            Some("MODEL") => arg.add(ArgValueCandidates::new(model_candidates)),
+            _ => arg,
+        }
+    });
+    let names: Vec<String> = command
+        .get_subcommands()
+        .map(|c| c.get_name().to_string())
+        .collect();
+    names.iter().fold(command, |command, name| {
+        command.mut_subcommand(name, add_candidates)
+    })
+}
+
+// Candidates are best effort: a broken config or store just completes nothing.
+
+fn profile_candidates() -> Vec<CompletionCandidate> {
+    let Ok(Some(config)) = Config::load() else {
+        return Vec::new();
+    };
+    config
+        .profile
+        .iter()
+        .map(|p| CompletionCandidate::new(&p.name))
+        .collect()
+}
+
+/// sessions of all profiles, as the profile is not known while completing
+fn session_candidates() -> Vec<CompletionCandidate> {
+    let config = Config::load().ok().flatten();
+    let Ok(store) = crate::store::open_configured_store(config.as_ref()) else {
+        return Vec::new();
+    };
+    let mut candidates: Vec<CompletionCandidate> = Vec::new();
+    for profile_name in store.list_profiles().unwrap_or_default() {
+        for session in store.load_sessions(&profile_name).unwrap_or_default() {
+            if candidates
+                .iter()
+                .all(|c| c.get_value() != session.name.as_str())
+            {
+                candidates.push(
+                    CompletionCandidate::new(&session.name)
+                        .help(Some(format!("profile {profile_name}").into())),
+                );
+            }
+        }
+    }
+    candidates
+}
+
+/// models of the catalogue, including ids cached by `kabeuchi models`
+fn model_candidates() -> Vec<CompletionCandidate> {
+    let config = Config::load().ok().flatten();
+    let Ok(catalogue) = Catalogue::load(config.as_ref()) else {
+        return Vec::new();
+    };
+    catalogue
+        .models
+        .iter()
+        .map(|m| CompletionCandidate::new(&m.name).hide(m.is_deprecated()))
+        .collect()
+}
@@ -0,0 +1,53 @@
+//! Environment variables which override the config file.
+//!
+//! Precedence, highest first:
+//! 1. command line flags (`--config`, `-p/--profile`, `--model`)
+//! 2. `KABEUCHI_*` environment variables
+//! 3. `[profile]` of the trusted project config (`.kabeuchi.toml`)
+//! 4. the profile in the config file, then its source profiles
+//! 5. `OPENAI_API_KEY` / `OPENAI_ORG_ID`, only when no profile sets them
+//! 6. defaults
+
+/// name of the profile used when `-p/--profile` is not given
+pub const KABEUCHI_PROFILE: &str = "KABEUCHI_PROFILE";
+/// directory which replaces `~/.config/kabeuchi` and holds everything, as the legacy layout
+pub const KABEUCHI_CONFIG_DIR: &str = "KABEUCHI_CONFIG_DIR";
+/// path of the config file, overridden by `--config`
+pub const KABEUCHI_CONFIG: &str = "KABEUCHI_CONFIG";
+pub const KABEUCHI_API_KEY: &str = "KABEUCHI_API_KEY";
+pub const KABEUCHI_ORG_ID: &str = "KABEUCHI_ORG_ID";
+pub const KABEUCHI_MODEL: &str = "KABEUCHI_MODEL";
+pub const KABEUCHI_TEMPERATURE: &str = "KABEUCHI_TEMPERATURE";
+pub const KABEUCHI_TOP_P: &str = "KABEUCHI_TOP_P";
+pub const KABEUCHI_MAX_TOKENS: &str = "KABEUCHI_MAX_TOKENS";
+pub const KABEUCHI_USE_HISTORY: &str = "KABEUCHI_USE_HISTORY";
+pub const KABEUCHI_USE_PRE_MESSAGES: &str = "KABEUCHI_USE_PRE_MESSAGES";
+/// ignore `.kabeuchi.toml` of the current directory and its parents when `true`
+pub const KABEUCHI_NO_PROJECT: &str = "KABEUCHI_NO_PROJECT";
+pub const XDG_CONFIG_HOME: &str = "XDG_CONFIG_HOME";
+pub const XDG_DATA_HOME: &str = "XDG_DATA_HOME";
+pub const XDG_STATE_HOME: &str = "XDG_STATE_HOME";
+pub const XDG_CACHE_HOME: &str = "XDG_CACHE_HOME";
+pub const OPENAI_API_KEY: &str = "OPENAI_API_KEY";
+pub const OPENAI_ORG_ID: &str = "OPENAI_ORG_ID";
+
+/// Value of the environment variable, treating an empty value as unset.
+pub fn get(name: &str) -> Option<String> {
+    std::env::var(name).ok().filter(|v| !v.is_empty())
+}
+
+/// Parse the environment variable with `FromStr`, if it is set.
+pub fn parse<T: std::str::FromStr>(name: &str) -> Result<Option<T>, String> {
+    match get(name) {
+        Some(value) => value
+            .parse()
+            .map(Some)
+            .map_err(|_| format!("invalid value of environment variable {name}: \"{value}\"")),
+        None => Ok(None),
+    }
+}
+
+/// Whether any variable that can make up a profile without the config file is set.
+pub fn has_api_key() -> bool {
+    get(KABEUCHI_API_KEY).is_some() || get(OPENAI_API_KEY).is_some()
+}
@@ -1,4 +1,12 @@
-use std::path::PathBuf;
+use fs2::FileExt;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// permissions of directories created by kabeuchi on Unix
+pub const MODE_PRIVATE_DIR: u32 = 0o700;
+/// permissions of files written by kabeuchi on Unix, which may hold API keys or conversations
+pub const MODE_PRIVATE_FILE: u32 = 0o600;
 
 pub fn load_text(path: &PathBuf) -> Result<String, String> {
     std::fs::read_to_string(path).map_err(|e| {
@@ -10,18 +18,234 @@ pub fn load_text(path: &PathBuf) -> Result<String, String> {
     })
 }
 
-pub fn save_text(path: &PathBuf, text: &str) -> Result<(), String> {
+fn create_parent_dir(path: &Path) -> Result<(), String> {
     let dir = path.parent().ok_or(format!(
         "failed to resolve parenet directory of file: path={}",
         path.display()
     ))?;
-    std::fs::create_dir_all(dir).map_err(|e| {
+    create_dir_private(dir).map_err(|e| {
         format!(
             "failed to create parent directory of path: path={}, err={}",
             path.display(),
             e
         )
-    })?;
-    std::fs::write(path, text)
+    })
+}
+
+/// `create_dir_all` which creates missing directories with `MODE_PRIVATE_DIR`.
+pub fn create_dir_private(dir: &Path) -> std::io::Result<()> {
+    let mut builder = std::fs::DirBuilder::new();
+    builder.recursive(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::DirBuilderExt;
+        builder.mode(MODE_PRIVATE_DIR);
+    }
+    builder.create(dir)
+}
+
+/// `OpenOptions` which creates missing files with `MODE_PRIVATE_FILE`.
+fn open_options_private() -> OpenOptions {
+    let mut options = OpenOptions::new();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(MODE_PRIVATE_FILE);
+    }
+    options
+}
+
+/// Permission bits of `path`, or `None` where they are not supported.
+pub fn get_mode(path: &Path) -> Option<u32> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::metadata(path)
+            .ok()
+            .map(|m| m.permissions().mode() & 0o777)
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = path;
+        None
+    }
+}
+
+pub fn set_mode(path: &Path, mode: u32) -> Result<(), String> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode)).map_err(|e| {
+            format!(
+                "failed to change permissions: path={}, err={}",
+                path.display(),
+                e
+            )
+        })
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = (path, mode);
+        Ok(())
+    }
+}
+
+/// Write to a temporary file next to `path` and rename it, so that `path` is never left truncated.
+/// A symlink is kept, and the file it points to is replaced instead.
+pub fn save_text(path: &Path, text: &str) -> Result<(), String> {
+    create_parent_dir(path)?;
+    let real_path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+
+    let file_name = real_path
+        .file_name()
+        .ok_or(format!("failed to get filename: path={}", path.display()))?
+        .to_string_lossy();
+    let path_tmp = real_path.with_file_name(format!(".{}.{}.tmp", file_name, std::process::id()));
+
+    let result = open_options_private()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&path_tmp)
+        .and_then(|mut file| {
+            file.write_all(text.as_bytes())?;
+            file.sync_all()
+        })
+        .and_then(|_| std::fs::rename(&path_tmp, &real_path));
+
+    if let Err(e) = result {
+        let _ = std::fs::remove_file(&path_tmp);
+        return Err(format!(
+            "failed to write file: path={}, err={}",
+            path.display(),
+            e
+        ));
+    }
+    Ok(())
+}
+
+pub fn append_text(path: &PathBuf, text: &str) -> Result<(), String> {
+    create_parent_dir(path)?;
+    let mut file = open_options_private()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| format!("failed to open file: path={}, err={}", path.display(), e))?;
+    file.write_all(text.as_bytes())
         .map_err(|e| format!("failed to write file: path={}, err={}", path.display(), e))
 }
+
+/// Take an advisory exclusive lock, blocking until it is available.
+/// The lock is released when the returned file is dropped.
+pub fn lock_exclusive(path: &PathBuf) -> Result<File, String> {
+    create_parent_dir(path)?;
+    let file = open_options_private()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(path)
+        .map_err(|e| format!("failed to open file: path={}, err={}", path.display(), e))?;
+
+    if file.try_lock_exclusive().is_err() {
+        eprintln!("waiting for lock: path={}", path.display());
+        file.lock_exclusive()
+            .map_err(|e| format!("failed to lock file: path={}, err={}", path.display(), e))?;
+    }
+    Ok(file)
+}
+
+/// Copy `from` to `to` recursively. `to` must not exist.
+pub fn copy_dir_all(from: &Path, to: &Path) -> Result<(), String> {
+    create_dir_private(to).map_err(|e| {
+        format!(
+            "failed to create directory: path={}, err={}",
+            to.display(),
+            e
+        )
+    })?;
+    let entries = std::fs::read_dir(from).map_err(|e| format!("failed to read directory: {e}"))?;
+    for entry in entries {
+        let entry =
+            entry.map_err(|e| format!("failed to parse result of reading directory: {e}"))?;
+        let path = entry.path();
+        let destination = to.join(entry.file_name());
+        if path.is_dir() {
+            copy_dir_all(&path, &destination)?;
+        } else {
+            std::fs::copy(&path, &destination).map_err(|e| {
+                format!(
+                    "failed to copy file: from={}, to={}, err={}",
+                    path.display(),
+                    destination.display(),
+                    e
+                )
+            })?;
+        }
+    }
+    Ok(())
+}
+
+/// Move a file or a directory, copying and removing it when `rename` fails across file systems.
+pub fn move_path(from: &Path, to: &Path) -> Result<(), String> {
+    if let Some(dir) = to.parent() {
+        create_dir_private(dir).map_err(|e| {
+            format!(
+                "failed to create directory: path={}, err={}",
+                dir.display(),
+                e
+            )
+        })?;
+    }
+    if std::fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+
+    let failed = |e: std::io::Error| {
+        format!(
+            "failed to move: from={}, to={}, err={}",
+            from.display(),
+            to.display(),
+            e
+        )
+    };
+    if from.is_dir() {
+        copy_dir_all(from, to)?;
+        std::fs::remove_dir_all(from).map_err(failed)
+    } else {
+        std::fs::copy(from, to).map_err(failed)?;
+        std::fs::remove_file(from).map_err(failed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn save_text_keeps_symlink() {
+        let dir =
+            std::env::temp_dir().join(format!("kabeuchi-test-{}-symlink", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("dotfiles")).unwrap();
+        let real = dir.join("dotfiles/config.toml");
+        let link = dir.join("config.toml");
+        std::fs::write(&real, "old").unwrap();
+        std::os::unix::fs::symlink(&real, &link).unwrap();
+
+        save_text(&link, "new").unwrap();
+        assert!(link.symlink_metadata().unwrap().file_type().is_symlink());
+        assert_eq!(std::fs::read_to_string(&real).unwrap(), "new");
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn save_text_creates_file() {
+        let dir = std::env::temp_dir().join(format!("kabeuchi-test-{}-save", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let path = dir.join("a/b.txt");
+        save_text(&path, "text").unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "text");
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,300 @@
+use crate::models::api::{Message, RequestChatCompletion, ResponseChatCompletion, Role};
+use crate::models::config::Profile;
+use crate::models::messages::{SavedMessage, Turn};
+use crate::models::session::{validate_session_name, Session, MAIN_SESSION};
+use crate::models::summary::HistorySummary;
+use crate::models::usage::UsageRecord;
+use crate::store::{SqliteHistoryStore, YamlHistoryStore};
+use crate::traits::history_store::HistoryStore;
+
+const SUMMARY_INSTRUCTION: &str = "Summarize the following conversation concisely. \
+Keep facts, decisions, names and open questions that are needed to continue the conversation. \
+Write the summary in the language of the conversation.";
+
+fn ensure_session(
+    store: &dyn HistoryStore,
+    profile_name: &str,
+    session_name: Option<&str>,
+) -> Result<(), String> {
+    if let Some(session_name) = session_name {
+        if store.load_session(profile_name, session_name)?.is_none() {
+            return Err(format!(
+                "Session \"{session_name}\" is not exists (profile: {profile_name})"
+            ));
+        }
+    }
+    Ok(())
+}
+
+pub fn get_turns(
+    store: &dyn HistoryStore,
+    profile_name: &str,
+    session_name: Option<&str>,
+) -> Result<Vec<Turn>, String> {
+    ensure_session(store, profile_name, session_name)?;
+    store.load_turns(profile_name, session_name)
+}
+
+/// Rough token count: about 4 ASCII characters or 1 non-ASCII character per token.
+pub fn estimate_tokens(text: &str) -> u64 {
+    let ascii = text.chars().filter(|c| c.is_ascii()).count() as u64;
+    let others = text.chars().count() as u64 - ascii;
+    ascii / 4 + others
+}
+
+fn get_text(message: &SavedMessage) -> &str {
+    match message {
+        SavedMessage::System(text) => text,
+        SavedMessage::Assistant(text) => text,
+        SavedMessage::User(text) => text,
+    }
+}
+
+fn summarize(
+    store: &dyn HistoryStore,
+    profile: &Profile,
+    session_name: Option<&str>,
+    summary: &Option<HistorySummary>,
+    turns: &[Turn],
+) -> Result<String, String> {
+    let mut transcript = String::new();
+    if let Some(summary) = summary {
+        transcript.push_str(&format!(
+            "summary of earlier conversation:\n{}\n\n",
+            summary.content
+        ));
+    }
+    for turn in turns {
+        for message in &turn.messages {
+            let role = match message {
+                SavedMessage::System(_) => "system",
+                SavedMessage::Assistant(_) => "assistant",
+                SavedMessage::User(_) => "user",
+            };
+            transcript.push_str(&format!("{role}: {}\n\n", get_text(message)));
+        }
+    }
+
+    let request = RequestChatCompletion {
+        model: profile.get_summary_model(),
+        messages: vec![
+            Message::new(Role::System, SUMMARY_INSTRUCTION),
+            Message::new(Role::User, &transcript),
+        ],
+        temperature: None,
+        top_p: None,
+        max_tokens: None,
+        presence_penalty: None,
+        frequency_penalty: None,
+        user: match &profile.user {
+            Some(user) => Some(user.clone()),
+            None => Some(profile.name.clone()),
+        },
+        tools: None,
+        tool_choice: None,
+    };
+
+    let response = crate::api::send_chat_completion(profile, &request)?;
+    save_usage(store, &profile.name, session_name, &response)?;
+    Ok(response.get_assistant_message())
+}
+
+/// Histories to send with a request. When `summary_threshold` is set and exceeded,
+/// older turns are condensed into a summary which is sent instead of them.
+pub fn get_histories_for_request(
+    store: &dyn HistoryStore,
+    profile: &Profile,
+    session_name: Option<&str>,
+) -> Result<Vec<SavedMessage>, String> {
+    let mut turns = if profile.get_lenient_history() {
+        ensure_session(store, &profile.name, session_name)?;
+        let (turns, errors) = store.load_turns_lenient(&profile.name, session_name)?;
+        for e in &errors {
+            eprintln!("skipped broken history: {e}");
+        }
+        if !errors.is_empty() {
+            eprintln!("run `kabeuchi doctor` to repair broken histories\n");
+        }
+        turns
+    } else {
+        get_turns(store, &profile.name, session_name)?
+    };
+    let mut summary = store.load_summary(&profile.name, session_name)?;
+    if let Some(summary) = &summary {
+        turns.retain(|t| t.id > summary.until);
+    }
+
+    if let Some(threshold) = profile.summary_threshold {
+        let tokens = summary
+            .as_ref()
+            .map(|s| estimate_tokens(&s.content))
+            .unwrap_or(0)
+            + turns
+                .iter()
+                .flat_map(|t| t.messages.iter())
+                .map(|m| estimate_tokens(get_text(m)))
+                .sum::<u64>();
+        let keep = profile.get_summary_keep_turns();
+
+        if tokens > threshold && turns.len() > keep {
+            let recent = turns.split_off(turns.len() - keep);
+            let content = summarize(store, profile, session_name, &summary, &turns)?;
+            let new_summary = HistorySummary {
+                content,
+                until: turns.last().map(|t| t.id.clone()).unwrap(),
+                model: profile.get_summary_model(),
+            };
+            store.save_summary(&profile.name, session_name, &new_summary)?;
+            summary = Some(new_summary);
+            turns = recent;
+        }
+    }
+
+    let mut result = Vec::new();
+    if let Some(summary) = summary {
+        result.push(SavedMessage::System(format!(
+            "Summary of the earlier conversation:\n{}",
+            summary.content
+        )));
+    }
+    for mut turn in turns {
+        result.append(&mut turn.messages);
+    }
+
+    Ok(result)
+}
+
+pub fn save_history(
+    store: &dyn HistoryStore,
+    profile_name: &str,
+    session_name: Option<&str>,
+    message: &str,
+    response: &ResponseChatCompletion,
+) -> Result<(), String> {
+    // `created` only has a resolution of seconds, so append the sub-second part of local time
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.subsec_nanos())
+        .unwrap_or(0);
+    let turn = Turn {
+        id: format!("{}_{:09}", response.created, nanos),
+        messages: vec![
+            SavedMessage::User(message.to_string()),
+            SavedMessage::Assistant(response.get_assistant_message()),
+        ],
+    };
+    store.save_turn(profile_name, session_name, &turn)
+}
+
+pub fn save_usage(
+    store: &dyn HistoryStore,
+    profile_name: &str,
+    session_name: Option<&str>,
+    response: &ResponseChatCompletion,
+) -> Result<(), String> {
+    if let Some(usage) = &response.usage {
+        store.save_usage(&UsageRecord {
+            profile: profile_name.to_string(),
+            session: session_name.map(|s| s.to_string()),
+            created: response.created as u64,
+            model: response.model.clone(),
+            prompt_tokens: usage.prompt_tokens,
+            completion_tokens: usage.completion_tokens,
+            total_tokens: usage.total_tokens,
+        })?;
+    }
+    Ok(())
+}
+
+pub fn fork_session(
+    store: &dyn HistoryStore,
+    profile_name: &str,
+    source_session_name: Option<&str>,
+    new_session_name: &str,
+    turn: usize,
+) -> Result<Session, String> {
+    validate_session_name(new_session_name)?;
+    if store
+        .load_session(profile_name, new_session_name)?
+        .is_some()
+    {
+        return Err(format!(
+            "Session \"{new_session_name}\" already exists (profile: {profile_name})"
+        ));
+    }
+
+    let turns = get_turns(store, profile_name, source_session_name)?;
+    if turn > turns.len() {
+        return Err(format!(
+            "turn {turn} is out of range: session \"{}\" has {} turns",
+            source_session_name.unwrap_or(MAIN_SESSION),
+            turns.len()
+        ));
+    }
+
+    for t in &turns[..turn] {
+        store.save_turn(profile_name, Some(new_session_name), t)?;
+    }
+
+    let session = Session {
+        name: new_session_name.to_string(),
+        parent: source_session_name.map(|s| s.to_string()),
+        forked_at: turn,
+    };
+    store.save_session(profile_name, &session)?;
+
+    Ok(session)
+}
+
+pub struct MigrationReport {
+    pub profiles: usize,
+    pub sessions: usize,
+    pub turns: usize,
+    pub usages: usize,
+}
+
+/// Copy everything in the YAML directory layout into the SQLite database.
+/// Turns, sessions and summaries are upserted, so running it again is safe.
+pub fn migrate_yaml_to_sqlite() -> Result<MigrationReport, String> {
+    let source = YamlHistoryStore;
+    let target = SqliteHistoryStore::open(&crate::path::get_path_history_db_file()?)?;
+
+    let mut report = MigrationReport {
+        profiles: 0,
+        sessions: 0,
+        turns: 0,
+        usages: 0,
+    };
+
+    for profile_name in source.list_profiles()? {
+        report.profiles += 1;
+
+        let sessions = source.load_sessions(&profile_name)?;
+        let mut session_names: Vec<Option<&str>> = vec![None];
+        session_names.extend(sessions.iter().map(|s| Some(s.name.as_str())));
+
+        for session in &sessions {
+            target.save_session(&profile_name, session)?;
+            report.sessions += 1;
+        }
+
+        for session_name in session_names {
+            for turn in source.load_turns(&profile_name, session_name)? {
+                target.save_turn(&profile_name, session_name, &turn)?;
+                report.turns += 1;
+            }
+            if let Some(summary) = source.load_summary(&profile_name, session_name)? {
+                target.save_summary(&profile_name, session_name, &summary)?;
+            }
+        }
+
+        if target.load_usages(&profile_name)?.is_empty() {
+            for usage in source.load_usages(&profile_name)? {
+                target.save_usage(&usage)?;
+                report.usages += 1;
+            }
+        }
+    }
+
+    Ok(report)
+}
@@ -1,13 +1,22 @@
 use std::fmt::Display;
 use std::io::Write;
+use std::path::PathBuf;
 use std::str::FromStr;
 
 pub fn get_input<T: Display>(message: T) -> Result<String, String> {
-    print!("{message}");
-    let mut input = String::new();
-    std::io::stdout()
-        .flush()
+    read_input(message, &mut std::io::stdout())
+}
+
+/// Like `get_input`, but the prompt goes to stderr so that it is not mixed into captured output.
+pub fn get_input_on_stderr<T: Display>(message: T) -> Result<String, String> {
+    read_input(message, &mut std::io::stderr())
+}
+
+fn read_input<T: Display>(message: T, out: &mut dyn Write) -> Result<String, String> {
+    write!(out, "{message}")
+        .and_then(|_| out.flush())
         .map_err(|e| format!("failed to write to terminal: {e}"))?;
+    let mut input = String::new();
     std::io::stdin()
         .read_line(&mut input)
         .map_err(|e| format!("failed to get input of terminal: {e}"))?;
@@ -16,14 +25,25 @@ pub fn get_input<T: Display>(message: T) -> Result<String, String> {
 }
 
 pub fn get_confirm<T: Display>(message: T, default: bool) -> Result<Option<bool>, String> {
-    let resp = get_input(message)?;
+    Ok(parse_confirm(&get_input(message)?, default))
+}
+
+/// Like `get_confirm`, but the prompt goes to stderr.
+pub fn get_confirm_on_stderr<T: Display>(
+    message: T,
+    default: bool,
+) -> Result<Option<bool>, String> {
+    Ok(parse_confirm(&get_input_on_stderr(message)?, default))
+}
+
+fn parse_confirm(resp: &str, default: bool) -> Option<bool> {
     match resp.to_lowercase().as_str() {
-        "" => Ok(Some(default)),
-        "y" => Ok(Some(true)),
-        "yes" => Ok(Some(true)),
-        "n" => Ok(Some(false)),
-        "no" => Ok(Some(false)),
-        _ => Ok(None),
+        "" => Some(default),
+        "y" => Some(true),
+        "yes" => Some(true),
+        "n" => Some(false),
+        "no" => Some(false),
+        _ => None,
     }
 }
 
@@ -54,3 +74,17 @@ pub fn get_input_number<T: Display + Copy, U: Display + Copy, V: FromStr + Displ
         Ok((None, None))
     }
 }
+
+pub fn open_editor(path: &PathBuf) -> Result<(), String> {
+    let editor = std::env::var("EDITOR")
+        .map_err(|e| format!("failed to get environment variable \"EDITOR\": {e}"))?;
+
+    std::process::Command::new(editor)
+        .arg(path)
+        .spawn()
+        .map_err(|e| format!("failed to spawn editor: {e}"))?
+        .wait()
+        .map_err(|e| format!("failed to edit message: {e}"))?;
+
+    Ok(())
+}
@@ -1,29 +1,76 @@
 mod api;
+mod catalogue;
 mod cmd;
+mod completion;
+mod env;
 mod fs;
+mod history;
 mod input;
+mod migration;
 mod models;
 mod path;
+mod pre_messages;
+mod project;
+mod secret;
+mod store;
+mod template;
+mod tools;
 mod traits;
 
 use crate::cmd::*;
 use crate::traits::command_definition::CommandDefinition;
 
-use clap::command;
+use clap::{arg, command, value_parser, Command};
+use std::path::PathBuf;
 
-fn main() -> Result<(), String> {
-    let matches = command!()
+/// The whole command tree, shared by parsing, shell completion and man pages.
+pub fn build_command() -> Command {
+    let command = command!()
         .subcommand_required(true)
         .arg_required_else_help(true)
+        .arg(
+            arg!(--config <PATH> "path of the config file (overrides KABEUCHI_CONFIG)")
+                .global(true)
+                .value_parser(value_parser!(PathBuf)),
+        )
         .subcommand(CmdPrompt::command())
         .subcommand(CmdPath::command())
         .subcommand(CmdConfigure::command())
-        .get_matches();
+        .subcommand(CmdConfig::command())
+        .subcommand(CmdProfile::command())
+        .subcommand(CmdHistory::command())
+        .subcommand(CmdDoctor::command())
+        .subcommand(CmdPreMessages::command())
+        .subcommand(CmdCatalogue::command())
+        .subcommand(CmdModels::command())
+        .subcommand(CmdCompletions::command())
+        .subcommand(CmdMan::command());
+    crate::completion::add_candidates(command)
+}
+
+fn main() -> Result<(), String> {
+    crate::completion::complete(build_command);
+    let matches = build_command().get_matches();
+
+    if let Some(path) = matches.get_one::<PathBuf>("config") {
+        crate::path::set_path_config_file(path.clone());
+    }
+    crate::path::migrate_legacy_layout()?;
+    crate::models::config::Config::migrate_file()?;
 
     match matches.subcommand() {
         Some((CmdPrompt::NAME, args)) => CmdPrompt::run(args),
         Some((CmdPath::NAME, args)) => CmdPath::run(args),
         Some((CmdConfigure::NAME, args)) => CmdConfigure::run(args),
+        Some((CmdConfig::NAME, args)) => CmdConfig::run(args),
+        Some((CmdProfile::NAME, args)) => CmdProfile::run(args),
+        Some((CmdHistory::NAME, args)) => CmdHistory::run(args),
+        Some((CmdDoctor::NAME, args)) => CmdDoctor::run(args),
+        Some((CmdPreMessages::NAME, args)) => CmdPreMessages::run(args),
+        Some((CmdCatalogue::NAME, args)) => CmdCatalogue::run(args),
+        Some((CmdModels::NAME, args)) => CmdModels::run(args),
+        Some((CmdCompletions::NAME, args)) => CmdCompletions::run(args),
+        Some((CmdMan::NAME, args)) => CmdMan::run(args),
         _ => unreachable!(),
     }
 }
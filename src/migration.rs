@@ -0,0 +1,222 @@
+//! Upgrade of `config.toml` written by older versions.
+//!
+//! Each migration takes the raw TOML of version `N` and rewrites it as version `N + 1`,
+//! so that old files are read without keeping old structs around.
+//! To change the schema, bump `CONFIG_VERSION` and append a migration to `MIGRATIONS`.
+
+use crate::models::profile_field::PROFILE_FIELDS;
+use toml::map::Map;
+use toml::Value;
+
+/// version of `config.toml` written by this build
+pub const CONFIG_VERSION: u32 = 1;
+
+type Migration = fn(&mut Map<String, Value>) -> Result<(), String>;
+
+/// `MIGRATIONS[n]` upgrades version `n` to `n + 1`
+const MIGRATIONS: &[Migration] = &[migrate_v0_to_v1];
+
+/// keys of the top level of `config.toml`
+const CONFIG_KEYS: &[&str] = &["version", "history_backend", "profile", "model"];
+/// keys of a profile which are not in `PROFILE_FIELDS`
+const PROFILE_EXTRA_KEYS: &[&str] = &["name", "vars"];
+
+/// v0 is the config without `version`, which has the same schema as v1.
+fn migrate_v0_to_v1(_table: &mut Map<String, Value>) -> Result<(), String> {
+    Ok(())
+}
+
+/// `version` of the raw config, 0 when it is missing.
+pub fn get_version(table: &Map<String, Value>) -> Result<u32, String> {
+    match table.get("version") {
+        None => Ok(0),
+        Some(Value::Integer(version)) => {
+            u32::try_from(*version).map_err(|_| format!("invalid version {version} of config file"))
+        }
+        Some(value) => Err(format!("invalid version {value} of config file")),
+    }
+}
+
+/// Upgrade the raw config to `CONFIG_VERSION`. Returns the version before the upgrade.
+pub fn migrate(table: &mut Map<String, Value>) -> Result<u32, String> {
+    let version = get_version(table)?;
+    if version > CONFIG_VERSION {
+        return Err(format!(
+            "config file is version {version}, but this kabeuchi supports up to version {CONFIG_VERSION}; please update kabeuchi"
+        ));
+    }
+    for migration in &MIGRATIONS[version as usize..] {
+        migration(table)?;
+    }
+    table.insert("version".to_string(), Value::Integer(CONFIG_VERSION as i64));
+    Ok(version)
+}
+
+/// Reject keys unknown to this version, suggesting the nearest known key for typos.
+pub fn check_unknown_keys(table: &Map<String, Value>) -> Result<(), String> {
+    for key in table.keys() {
+        if !CONFIG_KEYS.contains(&key.as_str()) {
+            return Err(unknown_key(key, CONFIG_KEYS.iter().copied(), "config file"));
+        }
+    }
+
+    let profile_keys = || {
+        PROFILE_FIELDS
+            .iter()
+            .map(|f| f.key)
+            .chain(PROFILE_EXTRA_KEYS.iter().copied())
+    };
+    let profiles = table.get("profile").and_then(|v| v.as_array());
+    for profile in profiles.into_iter().flatten().filter_map(|v| v.as_table()) {
+        let name = profile.get("name").and_then(|v| v.as_str()).unwrap_or("?");
+        for key in profile.keys() {
+            if !profile_keys().any(|k| k == key) {
+                return Err(unknown_key(
+                    key,
+                    profile_keys(),
+                    &format!("profile \"{name}\""),
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn unknown_key<'a>(key: &str, candidates: impl Iterator<Item = &'a str>, place: &str) -> String {
+    match did_you_mean(key, candidates) {
+        Some(candidate) => {
+            format!("unknown key \"{key}\" in {place} (did you mean \"{candidate}\"?)")
+        }
+        None => format!("unknown key \"{key}\" in {place}"),
+    }
+}
+
+/// The candidate nearest to `key` by edit distance, if it is close enough to be a typo.
+pub fn did_you_mean<'a>(key: &str, candidates: impl Iterator<Item = &'a str>) -> Option<&'a str> {
+    candidates
+        .map(|candidate| (edit_distance(key, candidate), candidate))
+        .filter(|(distance, candidate)| *distance <= (candidate.len() / 3).max(1))
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let cost = if ca == *cb { 0 } else { 1 };
+            current.push(
+                (previous[j] + cost)
+                    .min(previous[j + 1] + 1)
+                    .min(current[j] + 1),
+            );
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> Map<String, Value> {
+        toml::from_str(text).unwrap()
+    }
+
+    #[test]
+    fn migrate_v0_stamps_version() {
+        let text = "[[profile]]\nname = \"default\"\ndefault_persona = \"coder\"\n";
+        let mut table = parse(text);
+        assert_eq!(migrate(&mut table), Ok(0));
+        assert_eq!(get_version(&table), Ok(CONFIG_VERSION));
+        table.remove("version");
+        assert_eq!(table, parse(text));
+    }
+
+    #[test]
+    fn migrate_current_version_keeps_config() {
+        let text = format!(
+            "version = {CONFIG_VERSION}\n[[profile]]\nname = \"default\"\ndefault_persona = [\"coder\"]\n"
+        );
+        let mut table = parse(&text);
+        assert_eq!(migrate(&mut table), Ok(CONFIG_VERSION));
+        assert_eq!(table, parse(&text));
+    }
+
+    #[test]
+    fn migrate_rejects_newer_version() {
+        let mut table = parse(&format!("version = {}\n", CONFIG_VERSION + 1));
+        assert!(migrate(&mut table)
+            .unwrap_err()
+            .contains("please update kabeuchi"));
+    }
+
+    #[test]
+    fn get_version_rejects_invalid_version() {
+        assert!(get_version(&parse("version = -1\n")).is_err());
+        assert!(get_version(&parse("version = \"1\"\n")).is_err());
+    }
+
+    #[test]
+    fn check_unknown_keys_accepts_known_keys() {
+        let table = parse(
+            "version = 1\nhistory_backend = \"yaml\"\n[[profile]]\nname = \"default\"\ntemperature = 0.5\n[profile.vars]\nlang = \"ja\"\n[[model]]\nname = \"gpt-4o\"\n",
+        );
+        assert_eq!(check_unknown_keys(&table), Ok(()));
+    }
+
+    #[test]
+    fn check_unknown_keys_suggests_top_level_key() {
+        let table = parse("histroy_backend = \"yaml\"\n");
+        assert_eq!(
+            check_unknown_keys(&table),
+            Err("unknown key \"histroy_backend\" in config file (did you mean \"history_backend\"?)".to_string())
+        );
+    }
+
+    #[test]
+    fn check_unknown_keys_suggests_profile_key() {
+        let table = parse("[[profile]]\nname = \"work\"\ntemprature = 0.5\n");
+        assert_eq!(
+            check_unknown_keys(&table),
+            Err(
+                "unknown key \"temprature\" in profile \"work\" (did you mean \"temperature\"?)"
+                    .to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn check_unknown_keys_without_suggestion() {
+        let table = parse("foo = 1\n");
+        assert_eq!(
+            check_unknown_keys(&table),
+            Err("unknown key \"foo\" in config file".to_string())
+        );
+    }
+
+    #[test]
+    fn did_you_mean_picks_nearest_candidate() {
+        let candidates = ["profile", "model", "version"];
+        assert_eq!(
+            did_you_mean("profil", candidates.into_iter()),
+            Some("profile")
+        );
+        assert_eq!(did_you_mean("modle", candidates.into_iter()), None);
+        assert_eq!(
+            did_you_mean("modell", candidates.into_iter()),
+            Some("model")
+        );
+        assert_eq!(did_you_mean("xyz", candidates.into_iter()), None);
+    }
+
+    #[test]
+    fn edit_distance_counts_edits() {
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("same", "same"), 0);
+    }
+}
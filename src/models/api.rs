@@ -8,29 +8,89 @@ pub enum Role {
     User,
     Assistant,
     System,
+    /// result of a tool call
+    Tool,
+    /// result of a function call of the deprecated `functions` API, kept to read old messages
+    Function,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Message {
     pub role: Role,
-    pub content: String,
+    /// `None` when the assistant only calls tools
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_calls: Option<Vec<ToolCall>>,
+    /// id of the tool call which a `tool` message answers
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_call_id: Option<String>,
+    /// name of the function which a `function` message answers
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+}
+
+impl Message {
+    pub fn new(role: Role, content: &str) -> Message {
+        Message {
+            role,
+            content: Some(content.to_string()),
+            tool_calls: None,
+            tool_call_id: None,
+            name: None,
+        }
+    }
+
+    pub fn tool_result(tool_call: &ToolCall, content: &str) -> Message {
+        Message {
+            tool_call_id: Some(tool_call.id.clone()),
+            ..Message::new(Role::Tool, content)
+        }
+    }
+
+    pub fn get_content(&self) -> &str {
+        self.content.as_deref().unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FunctionCall {
+    pub name: String,
+    /// JSON object of the arguments, which may be invalid
+    pub arguments: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolCall {
+    pub id: String,
+    /// always `function` for now
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub function: FunctionCall,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FunctionDefinition {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// JSON schema of the arguments
+    pub parameters: serde_json::Value,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Tool {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub function: FunctionDefinition,
 }
 
 impl From<&SavedMessage> for Message {
     fn from(value: &SavedMessage) -> Self {
         match value {
-            SavedMessage::System(text) => Message {
-                role: Role::System,
-                content: text.clone(),
-            },
-            SavedMessage::Assistant(text) => Message {
-                role: Role::Assistant,
-                content: text.clone(),
-            },
-            SavedMessage::User(text) => Message {
-                role: Role::User,
-                content: text.clone(),
-            },
+            SavedMessage::System(text) => Message::new(Role::System, text),
+            SavedMessage::Assistant(text) => Message::new(Role::Assistant, text),
+            SavedMessage::User(text) => Message::new(Role::User, text),
         }
     }
 }
@@ -55,6 +115,11 @@ pub struct RequestChatCompletion {
     // logit_bias: ??
     #[serde(skip_serializing_if = "Option::is_none")]
     pub user: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tools: Option<Vec<Tool>>,
+    /// `"none"`, `"auto"`, `"required"` or `{"type": "function", "function": {"name": ...}}`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_choice: Option<serde_json::Value>,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -67,14 +132,10 @@ pub struct TokenUsage {
 #[derive(Debug, Clone, Deserialize)]
 pub struct Choice {
     pub message: Message,
-    pub finish_reason: String,
-    pub index: u32,
 }
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct ResponseChatCompletion {
-    pub id: String,
-    pub object: String,
     pub created: u32,
     pub model: String,
     pub usage: Option<TokenUsage>,
@@ -83,6 +144,53 @@ pub struct ResponseChatCompletion {
 
 impl ResponseChatCompletion {
     pub fn get_assistant_message(&self) -> String {
-        self.choices[0].message.content.clone()
+        self.choices[0].message.get_content().to_string()
+    }
+
+    /// tool calls requested by the assistant, empty when it answered
+    pub fn get_tool_calls(&self) -> &[ToolCall] {
+        self.choices[0]
+            .message
+            .tool_calls
+            .as_deref()
+            .unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ModelObject {
+    pub id: String,
+}
+
+/// response of `GET /v1/models`
+#[derive(Debug, Clone, Deserialize)]
+pub struct ResponseListModels {
+    pub data: Vec<ModelObject>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn legacy_function_message_is_kept() {
+        let json = r#"{"role":"function","content":"sunny","name":"get_weather"}"#;
+        let message: Message = serde_json::from_str(json).unwrap();
+        assert!(matches!(message.role, Role::Function));
+        assert_eq!(message.name.as_deref(), Some("get_weather"));
+        assert_eq!(serde_json::to_string(&message).unwrap(), json);
+    }
+
+    #[test]
+    fn tool_result_answers_tool_call() {
+        let tool_call: ToolCall = serde_json::from_str(
+            r#"{"id":"call_1","type":"function","function":{"name":"now","arguments":"{}"}}"#,
+        )
+        .unwrap();
+        let json = serde_json::to_string(&Message::tool_result(&tool_call, "12:00")).unwrap();
+        assert_eq!(
+            json,
+            r#"{"role":"tool","content":"12:00","tool_call_id":"call_1"}"#
+        );
     }
 }
@@ -1,20 +1,53 @@
-use serde::{Deserialize, Serialize};
+use crate::models::model_info::ModelInfo;
+use crate::project::Project;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::cell::OnceCell;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
 
 pub const DEFAULT_MODEL: &str = "gpt-3.5-turbo";
+/// default of `temperature` on the API side when it is not sent
+pub const DEFAULT_TEMPERATURE: f32 = 1.0;
+/// fields holding the API key or where to read it from
+pub const API_KEY_SOURCES: &[&str] = &["api_key", "api_key_env", "api_key_command", "api_key_file"];
+/// the only API provider supported now
+pub const PROVIDER_OPENAI: &str = "openai";
+pub const DEFAULT_SUMMARY_KEEP_TURNS: usize = 4;
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
 pub struct Profile {
     pub name: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub source_profile: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub api_key: Option<String>,
+    /// name of the environment variable holding the API key
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub api_key_env: Option<String>,
+    /// command printing the API key to stdout, e.g. `pass show openai`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub api_key_command: Option<String>,
+    /// file holding the API key, which must not be readable by others
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub api_key_file: Option<PathBuf>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub organization_id: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub use_history: Option<bool>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub lenient_history: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub use_pre_messages: Option<bool>,
+    /// also send pre messages of source profiles (the farthest ancestor first)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub inherit_pre_messages: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub summary_threshold: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub summary_model: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub summary_keep_turns: Option<usize>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub model: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -33,105 +66,335 @@ pub struct Profile {
     // logit_bias: ??
     #[serde(skip_serializing_if = "Option::is_none")]
     pub user: Option<String>,
+    /// persona sets appended to pre messages when `--persona` is not given
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "deserialize_one_or_many"
+    )]
+    pub default_persona: Option<Vec<String>>,
+    /// default values of template variables in pre messages
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vars: Option<BTreeMap<String, String>>,
+    /// names of source profiles followed by `resolve`, nearest first
+    #[serde(skip)]
+    pub resolved_sources: Vec<String>,
+    /// `.kabeuchi/pre_messages` of the project applied by `resolve`
+    #[serde(skip)]
+    pub project_pre_messages_dir: Option<PathBuf>,
+    /// API key read from one of the sources by `get_api_key`
+    #[serde(skip)]
+    api_key_cache: OnceCell<String>,
+}
+
+/// Accept both `key = "a"` and `key = ["a", "b"]`.
+fn deserialize_one_or_many<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Vec<String>>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(String),
+        Many(Vec<String>),
+    }
+
+    Ok(match Option::<OneOrMany>::deserialize(deserializer)? {
+        Some(OneOrMany::One(value)) => Some(vec![value]),
+        Some(OneOrMany::Many(values)) => Some(values),
+        None => None,
+    })
+}
+
+/// A profile name is used as a directory name of pre messages, tools and history,
+/// so it must not be a path.
+pub fn validate_profile_name(name: &str) -> Result<(), String> {
+    if name.is_empty() {
+        return Err("profile name is empty".to_string());
+    }
+    if name.contains(['/', '\\']) || name.contains("..") {
+        return Err(format!(
+            "invalid profile name \"{name}\" (path separators and \"..\" are not allowed)"
+        ));
+    }
+    Ok(())
 }
 
 impl Profile {
-    fn merge(&mut self, profile: &Profile) {
-        if self.api_key.is_none() && profile.api_key.is_some() {
-            self.api_key = profile.api_key.clone();
+    pub fn new(name: &str) -> Profile {
+        Profile {
+            name: name.to_string(),
+            ..Default::default()
         }
+    }
 
-        if self.use_history.is_none() && profile.use_history.is_some() {
-            self.use_history = profile.use_history;
-        }
+    /// Fill fields unset in `self` with those of `profile`. `vars` are merged by name.
+    /// The sources of API key are taken together so that the nearest profile decides the key.
+    pub fn merge(&mut self, profile: &Profile) -> Result<(), String> {
+        let has_api_key_source = self.has_api_key_source();
+        let mut table = self.to_table()?;
 
-        if self.model.is_none() && profile.temperature.is_some() {
-            self.model = profile.model.clone();
+        for (key, value) in profile.to_table()? {
+            match key.as_str() {
+                "name" | "source_profile" => {}
+                key if API_KEY_SOURCES.contains(&key) => {
+                    if !has_api_key_source {
+                        table.insert(key.to_string(), value);
+                    }
+                }
+                "vars" => {
+                    let merged = match (value, table.remove("vars")) {
+                        (toml::Value::Table(mut vars), Some(toml::Value::Table(own))) => {
+                            vars.extend(own);
+                            toml::Value::Table(vars)
+                        }
+                        (value, _) => value,
+                    };
+                    table.insert(key, merged);
+                }
+                _ => {
+                    table.entry(key).or_insert(value);
+                }
+            }
         }
 
-        if self.temperature.is_none() && profile.temperature.is_some() {
-            self.temperature = profile.temperature;
-        }
+        self.replace_with_table(table)
+    }
 
-        if self.top_p.is_none() && profile.top_p.is_some() {
-            self.top_p = profile.top_p;
-        }
+    /// Follow `source_profile` and merge the source profiles, nearest first.
+    pub fn inherit(&self, list: &[Profile]) -> Result<Profile, String> {
+        let mut result = self.clone();
+        let mut chain = vec![self.name.clone()];
+        let mut current = self;
 
-        if self.max_tokens.is_none() && profile.max_tokens.is_some() {
-            self.max_tokens = profile.max_tokens;
-        }
+        while let Some(source_profile) = &current.source_profile {
+            if chain.contains(source_profile) {
+                chain.push(source_profile.clone());
+                return Err(format!("source_profile cycle: {}", chain.join(" -> ")));
+            }
+            let profile = list
+                .iter()
+                .find(|p| &p.name == source_profile)
+                .ok_or(format!(
+                    "source_profile \"{source_profile}\" of profile \"{}\" is not exists",
+                    current.name
+                ))?;
 
-        if self.presence_penalty.is_none() && profile.presence_penalty.is_some() {
-            self.presence_penalty = profile.presence_penalty;
+            result.merge(profile)?;
+            result.resolved_sources.push(profile.name.clone());
+            chain.push(profile.name.clone());
+            current = profile;
         }
 
-        if self.frequency_penalty.is_none() && profile.frequency_penalty.is_some() {
-            self.frequency_penalty = profile.frequency_penalty;
+        Ok(result)
+    }
+
+    /// Follow `source_profile`, then apply the project config and environment variables
+    /// (see `crate::env` for precedence).
+    pub fn resolve(&self, list: &[Profile], project: Option<&Project>) -> Result<Profile, String> {
+        let mut result = self.inherit(list)?;
+
+        if let Some(project) = project {
+            result.apply_project(project)?;
         }
+        result.apply_env()?;
 
-        if self.user.is_none() && profile.user.is_some() {
-            self.user = profile.user.clone();
+        if !result.has_api_key_source() {
+            return Err(if self.source_profile.is_some() {
+                "no token after resolving source profile".to_string()
+            } else {
+                "no exists, token or source_profile".to_string()
+            });
         }
+
+        Ok(result)
     }
 
-    pub fn resolve(&self, list: &[Profile]) -> Result<Profile, String> {
-        let mut result = self.clone();
+    fn apply_project(&mut self, project: &Project) -> Result<(), String> {
+        let mut profile = project.config.to_profile(&self.name)?;
+        profile.source_profile = self.source_profile.clone();
+        profile.merge(self)?;
+        profile.resolved_sources = std::mem::take(&mut self.resolved_sources);
+        profile.project_pre_messages_dir = Some(project.get_path_pre_messages_dir());
+        *self = profile;
+        Ok(())
+    }
 
-        if self.api_key.is_none() && self.source_profile.is_none() {
-            return Err("no exists, token or source_profile".to_string());
+    fn apply_env(&mut self) -> Result<(), String> {
+        use crate::env;
+
+        if let Some(api_key) = env::get(env::KABEUCHI_API_KEY) {
+            self.api_key = Some(api_key);
+        }
+        if let Some(organization_id) = env::get(env::KABEUCHI_ORG_ID) {
+            self.organization_id = Some(organization_id);
+        }
+        if let Some(model) = env::get(env::KABEUCHI_MODEL) {
+            self.model = Some(model);
+        }
+        if let Some(temperature) = env::parse(env::KABEUCHI_TEMPERATURE)? {
+            self.temperature = Some(temperature);
+        }
+        if let Some(top_p) = env::parse(env::KABEUCHI_TOP_P)? {
+            self.top_p = Some(top_p);
+        }
+        if let Some(max_tokens) = env::parse(env::KABEUCHI_MAX_TOKENS)? {
+            self.max_tokens = Some(max_tokens);
+        }
+        if let Some(use_history) = env::parse(env::KABEUCHI_USE_HISTORY)? {
+            self.use_history = Some(use_history);
+        }
+        if let Some(use_pre_messages) = env::parse(env::KABEUCHI_USE_PRE_MESSAGES)? {
+            self.use_pre_messages = Some(use_pre_messages);
         }
 
-        if self.source_profile.is_some() {
-            let mut current_source_profile = self.source_profile.clone();
+        if !self.has_api_key_source() {
+            self.api_key = env::get(env::OPENAI_API_KEY);
+        }
+        if self.organization_id.is_none() {
+            self.organization_id = env::get(env::OPENAI_ORG_ID);
+        }
 
-            while current_source_profile.is_some() {
-                let source_profile = &current_source_profile.clone().unwrap();
-                let profile = list.iter().find(|p| p.name.eq(source_profile));
+        Ok(())
+    }
 
-                if let Some(profile) = profile {
-                    current_source_profile = profile.source_profile.clone();
-                    result.merge(profile);
-                } else {
-                    current_source_profile = None;
-                }
-            }
+    pub fn has_api_key_source(&self) -> bool {
+        self.api_key.is_some()
+            || self.api_key_env.is_some()
+            || self.api_key_command.is_some()
+            || self.api_key_file.is_some()
+    }
 
-            if result.api_key.is_none() {
-                return Err("no token after resolving source profile".to_string());
-            }
+    /// Read the API key from the first source set, in the order of
+    /// `api_key`, `api_key_env`, `api_key_command` and `api_key_file`.
+    /// The sources are read only when a request is sent, and only once.
+    pub fn get_api_key(&self) -> Result<String, String> {
+        if let Some(api_key) = self.api_key_cache.get() {
+            return Ok(api_key.clone());
         }
 
-        Ok(result)
+        let api_key = if let Some(api_key) = &self.api_key {
+            api_key.clone()
+        } else if let Some(name) = &self.api_key_env {
+            crate::env::get(name).ok_or(format!(
+                "environment variable \"{name}\" of api_key_env is not set"
+            ))?
+        } else if let Some(command) = &self.api_key_command {
+            crate::secret::read_command(command)?
+        } else if let Some(path) = &self.api_key_file {
+            crate::secret::read_file(path)?
+        } else {
+            return Err("failed to get token (token is empty)".to_string());
+        };
+
+        if api_key.is_empty() {
+            return Err("failed to get token (token is empty)".to_string());
+        }
+        let _ = self.api_key_cache.set(api_key.clone());
+        Ok(api_key)
     }
 
     pub fn get_use_pre_messages(&self) -> bool {
         self.use_pre_messages.unwrap_or(true)
     }
 
+    pub fn get_inherit_pre_messages(&self) -> bool {
+        self.inherit_pre_messages.unwrap_or(false)
+    }
+
     pub fn get_use_history(&self) -> bool {
         self.use_history.unwrap_or(false)
     }
+
+    pub fn get_lenient_history(&self) -> bool {
+        self.lenient_history.unwrap_or(false)
+    }
+
+    pub fn get_model(&self) -> String {
+        match &self.model {
+            Some(model) => model.to_string(),
+            None => DEFAULT_MODEL.to_string(),
+        }
+    }
+
+    pub fn get_provider(&self) -> &'static str {
+        PROVIDER_OPENAI
+    }
+
+    pub fn get_temperature(&self) -> f32 {
+        self.temperature.unwrap_or(DEFAULT_TEMPERATURE)
+    }
+
+    pub fn get_summary_model(&self) -> String {
+        match &self.summary_model {
+            Some(model) => model.to_string(),
+            None => self.get_model(),
+        }
+    }
+
+    pub fn get_summary_keep_turns(&self) -> usize {
+        self.summary_keep_turns
+            .unwrap_or(DEFAULT_SUMMARY_KEEP_TURNS)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HistoryBackend {
+    #[default]
+    Yaml,
+    Sqlite,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
 pub struct Config {
+    /// schema version, see `crate::migration`
+    #[serde(default)]
+    pub version: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub history_backend: Option<HistoryBackend>,
     pub profile: Vec<Profile>,
+    /// models added to or overriding the bundled catalogue, see `crate::catalogue`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub model: Vec<ModelInfo>,
+    /// project found by `load_with_project`
+    #[serde(skip)]
+    pub project: Option<Project>,
 }
 
 impl Config {
     pub fn new() -> Config {
         Config {
+            version: crate::migration::CONFIG_VERSION,
+            history_backend: None,
             profile: Vec::new(),
+            model: Vec::new(),
+            project: None,
         }
     }
 
+    pub fn get_history_backend(&self) -> HistoryBackend {
+        self.history_backend.clone().unwrap_or_default()
+    }
+
+    /// Without the config file, an empty config is returned when an API key is given by
+    /// environment variables, so that profiles can be made up of them alone.
     pub fn load() -> Result<Option<Config>, String> {
         let path = crate::path::get_path_config_file()?;
         if !path.exists() {
+            if crate::env::has_api_key() {
+                return Ok(Some(Config::new()));
+            }
             return Ok(None);
         }
+        if let Some(mode) = crate::fs::get_mode(&path).filter(|mode| mode & 0o044 != 0) {
+            eprintln!(
+                "warning: config file is readable by other users (mode {mode:o}): path={}\n         run `kabeuchi doctor --fix` to restrict the permissions",
+                path.display()
+            );
+        }
         let text = crate::fs::load_text(&path)?;
-        toml::from_str(&text).map_err(|e| {
+        Config::parse(&text).map(Some).map_err(|e| {
             format!(
                 "failed to deserialize config file: path={}, err={}",
                 path.display(),
@@ -140,6 +403,103 @@ impl Config {
         })
     }
 
+    /// Parse `config.toml`, upgrading older versions in memory and rejecting unknown keys.
+    pub fn parse(text: &str) -> Result<Config, String> {
+        let mut table: toml::Table = toml::from_str(text).map_err(|e| e.to_string())?;
+        crate::migration::migrate(&mut table)?;
+        crate::migration::check_unknown_keys(&table)?;
+        toml::Value::Table(table)
+            .try_into()
+            .map_err(|e| e.to_string())
+    }
+
+    /// Upgrade the config file of an older version in place, keeping the original as
+    /// `config.toml.v<N>.bak`. A broken file is left for `load` to report.
+    /// The lock is taken only when the file needs the upgrade.
+    pub fn migrate_file() -> Result<(), String> {
+        let path = crate::path::get_path_config_file()?;
+        // text, raw config and version of the file when it is older than this build
+        let read_outdated = || -> Result<Option<(String, toml::Table, u32)>, String> {
+            if !path.exists() {
+                return Ok(None);
+            }
+            let text = crate::fs::load_text(&path)?;
+            let Ok(table) = toml::from_str::<toml::Table>(&text) else {
+                return Ok(None);
+            };
+            match crate::migration::get_version(&table) {
+                Ok(version) if version < crate::migration::CONFIG_VERSION => {
+                    Ok(Some((text, table, version)))
+                }
+                _ => Ok(None),
+            }
+        };
+        if read_outdated()?.is_none() {
+            return Ok(());
+        }
+        let _lock = crate::fs::lock_exclusive(&crate::path::get_path_config_lock_file()?)?;
+        // another process may have upgraded it while waiting for the lock
+        let Some((text, mut table, version)) = read_outdated()? else {
+            return Ok(());
+        };
+
+        crate::migration::migrate(&mut table)?;
+        let mut backup = path.clone().into_os_string();
+        backup.push(format!(".v{version}.bak"));
+        let backup = PathBuf::from(backup);
+        crate::fs::save_text(&backup, &text)?;
+        // unknown keys are kept for `load` to report
+        let migrated = match toml::Value::Table(table.clone()).try_into::<Config>() {
+            Ok(config) => toml::to_string_pretty(&config),
+            Err(_) => toml::to_string_pretty(&table),
+        }
+        .map_err(|e| format!("failed to serialize config: {e}"))?;
+        crate::fs::save_text(&path, &migrated)?;
+        eprintln!(
+            "upgraded config file from version {version} to {}: path={}, backup={}\n         comments and formatting were not kept; the original file is in the backup",
+            crate::migration::CONFIG_VERSION,
+            path.display(),
+            backup.display()
+        );
+        Ok(())
+    }
+
+    /// `load` with the trusted project config of the current directory layered over.
+    pub fn load_with_project() -> Result<Option<Config>, String> {
+        let Some(mut config) = Config::load()? else {
+            return Ok(None);
+        };
+        config.project = crate::project::load_project()?;
+        Ok(Some(config))
+    }
+
+    /// Reload the config under the config lock, apply `f` and save it,
+    /// so that changes written by another process meanwhile are not lost.
+    pub fn update<F: FnOnce(&mut Config)>(f: F) -> Result<Config, String> {
+        let _lock = crate::fs::lock_exclusive(&crate::path::get_path_config_lock_file()?)?;
+        let mut config = Config::load()?.unwrap_or_else(Config::new);
+        let broken = config.get_inherit_errors();
+        f(&mut config);
+        // a `source_profile` cycle or a missing source made by `f` is not saved
+        if let Some(e) = config
+            .get_inherit_errors()
+            .into_iter()
+            .find(|e| !broken.contains(e))
+        {
+            return Err(e);
+        }
+        config.save()?;
+        Ok(config)
+    }
+
+    /// Errors of following `source_profile` of each profile.
+    fn get_inherit_errors(&self) -> Vec<String> {
+        self.profile
+            .iter()
+            .filter_map(|p| p.inherit(&self.profile).err())
+            .collect()
+    }
+
     pub fn save(&self) -> Result<(), String> {
         let path = crate::path::get_path_config_file()?;
         let text = toml::to_string_pretty(&self)
@@ -155,7 +515,7 @@ impl Config {
         for c_profile in &self.profile {
             if c_profile.name == profile_name {
                 if is_resolve {
-                    let r_profile = c_profile.resolve(&self.profile)?;
+                    let r_profile = c_profile.resolve(&self.profile, self.project.as_ref())?;
                     return Ok(Some(r_profile));
                 } else {
                     return Ok(Some(c_profile.clone()));
@@ -163,6 +523,11 @@ impl Config {
             }
         }
 
+        if is_resolve && crate::env::has_api_key() {
+            let profile = Profile::new(profile_name);
+            return Ok(Some(profile.resolve(&self.profile, self.project.as_ref())?));
+        }
+
         Ok(None)
     }
 
@@ -175,3 +540,59 @@ impl Config {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_stamps_version_of_legacy_config() {
+        let config =
+            Config::parse("[[profile]]\nname = \"default\"\ndefault_persona = \"coder\"\n")
+                .unwrap();
+        assert_eq!(config.version, crate::migration::CONFIG_VERSION);
+        assert_eq!(
+            config.profile[0].default_persona,
+            Some(vec!["coder".to_string()])
+        );
+    }
+
+    #[test]
+    fn validate_profile_name_rejects_paths() {
+        assert!(validate_profile_name("work-2").is_ok());
+        assert!(validate_profile_name("").is_err());
+        assert!(validate_profile_name("../../x").is_err());
+        assert!(validate_profile_name("a/b").is_err());
+        assert!(validate_profile_name("a\\b").is_err());
+    }
+
+    #[test]
+    fn inherit_errors_report_cycle_and_missing_source() {
+        let mut config = Config::parse(
+            "[[profile]]\nname = \"a\"\n[[profile]]\nname = \"b\"\nsource_profile = \"a\"\n",
+        )
+        .unwrap();
+        assert!(config.get_inherit_errors().is_empty());
+
+        config.profile[0].source_profile = Some("b".to_string());
+        assert_eq!(
+            config.get_inherit_errors(),
+            [
+                "source_profile cycle: a -> b -> a",
+                "source_profile cycle: b -> a -> b"
+            ]
+        );
+
+        config.profile[0].source_profile = Some("c".to_string());
+        assert_eq!(
+            config.get_inherit_errors()[0],
+            "source_profile \"c\" of profile \"a\" is not exists"
+        );
+    }
+
+    #[test]
+    fn parse_rejects_unknown_key() {
+        let err = Config::parse("[[profile]]\nname = \"default\"\ntemprature = 0.5\n").unwrap_err();
+        assert!(err.contains("did you mean \"temperature\"?"));
+    }
+}
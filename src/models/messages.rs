@@ -10,6 +10,7 @@ pub enum SavedMessage {
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
 pub struct RawSavedMessage {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub system: Option<String>,
@@ -52,3 +53,10 @@ impl TryFrom<RawSavedMessage> for SavedMessage {
         }
     }
 }
+
+/// A pair of messages saved per response. `id` orders turns in a history.
+#[derive(Debug, Clone)]
+pub struct Turn {
+    pub id: String,
+    pub messages: Vec<SavedMessage>,
+}
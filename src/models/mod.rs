@@ -1,3 +1,11 @@
 pub mod api;
 pub mod config;
 pub mod messages;
+pub mod model_info;
+pub mod pre_message;
+pub mod profile_field;
+pub mod project;
+pub mod session;
+pub mod summary;
+pub mod tool;
+pub mod usage;
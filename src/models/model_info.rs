@@ -0,0 +1,110 @@
+use serde::{Deserialize, Serialize};
+
+/// A model of the catalogue. Unknown values are `None`.
+/// `[[model]]` of `config.toml` overrides the bundled entry of the same name field by field.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ModelInfo {
+    pub name: String,
+    /// tokens of the prompt and the completion together
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub context_window: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_output_tokens: Option<u64>,
+    /// USD per 1M prompt tokens
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub input_price: Option<f64>,
+    /// USD per 1M completion tokens
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output_price: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub supports_streaming: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub supports_tools: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub supports_vision: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deprecated: Option<bool>,
+    /// model to use instead of a deprecated one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub replacement: Option<String>,
+}
+
+impl ModelInfo {
+    pub fn new(name: &str) -> ModelInfo {
+        ModelInfo {
+            name: name.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Override fields set in `other`.
+    pub fn merge(&mut self, other: &ModelInfo) {
+        self.context_window = other.context_window.or(self.context_window);
+        self.max_output_tokens = other.max_output_tokens.or(self.max_output_tokens);
+        self.input_price = other.input_price.or(self.input_price);
+        self.output_price = other.output_price.or(self.output_price);
+        self.supports_streaming = other.supports_streaming.or(self.supports_streaming);
+        self.supports_tools = other.supports_tools.or(self.supports_tools);
+        self.supports_vision = other.supports_vision.or(self.supports_vision);
+        self.deprecated = other.deprecated.or(self.deprecated);
+        if other.replacement.is_some() {
+            self.replacement = other.replacement.clone();
+        }
+    }
+
+    pub fn is_deprecated(&self) -> bool {
+        self.deprecated.unwrap_or(false)
+    }
+
+    /// Estimated cost in USD, if the prices are known.
+    pub fn estimate_cost(&self, prompt_tokens: u64, completion_tokens: u64) -> Option<f64> {
+        Some(
+            (self.input_price? * prompt_tokens as f64
+                + self.output_price? * completion_tokens as f64)
+                / 1_000_000.0,
+        )
+    }
+
+    /// e.g. `streaming, tools`
+    pub fn describe_capabilities(&self) -> String {
+        let capabilities: Vec<&str> = [
+            (self.supports_streaming, "streaming"),
+            (self.supports_tools, "tools"),
+            (self.supports_vision, "vision"),
+        ]
+        .iter()
+        .filter(|(supported, _)| supported.unwrap_or(false))
+        .map(|(_, name)| *name)
+        .collect();
+        capabilities.join(", ")
+    }
+}
+
+/// Model ids fetched from `/v1/models`, cached in the cache dir.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct FetchedModels {
+    /// unix time in seconds
+    pub fetched_at: u64,
+    /// profile whose credentials were used
+    pub profile: String,
+    pub ids: Vec<String>,
+}
+
+impl FetchedModels {
+    pub fn is_fresh(&self) -> bool {
+        let now = chrono::Utc::now().timestamp() as u64;
+        now.saturating_sub(self.fetched_at) < crate::catalogue::MODELS_CACHE_TTL_SECS
+    }
+
+    /// `fetched_at` in local time
+    pub fn format_fetched_at(&self) -> String {
+        chrono::DateTime::from_timestamp(self.fetched_at as i64, 0)
+            .map(|t| {
+                t.with_timezone(&chrono::Local)
+                    .format("%Y-%m-%d %H:%M")
+                    .to_string()
+            })
+            .unwrap_or_default()
+    }
+}
@@ -0,0 +1,164 @@
+use crate::models::config::Profile;
+use crate::models::messages::SavedMessage;
+use serde::Deserialize;
+use std::convert::TryFrom;
+
+/// Conditions under which a pre message is sent. Unset conditions always match.
+#[derive(Debug, Clone, Default)]
+pub struct PreMessageCondition {
+    /// globs of model names, e.g. `gpt-4*`
+    pub models: Option<Vec<String>>,
+    /// names of API providers, e.g. `openai`
+    pub providers: Option<Vec<String>>,
+    /// sent when any of the tags is given by `--tag`
+    pub tags: Option<Vec<String>>,
+    pub min_temperature: Option<f32>,
+    pub max_temperature: Option<f32>,
+}
+
+impl PreMessageCondition {
+    pub fn matches(&self, profile: &Profile, tags: &[String]) -> bool {
+        if let Some(models) = &self.models {
+            let model = profile.get_model();
+            if !models.iter().any(|m| match glob::Pattern::new(m) {
+                Ok(pattern) => pattern.matches(&model),
+                Err(_) => m == &model,
+            }) {
+                return false;
+            }
+        }
+
+        if let Some(providers) = &self.providers {
+            if !providers.iter().any(|p| p == profile.get_provider()) {
+                return false;
+            }
+        }
+
+        if let Some(condition_tags) = &self.tags {
+            if !condition_tags.iter().any(|t| tags.contains(t)) {
+                return false;
+            }
+        }
+
+        let temperature = profile.get_temperature();
+        if self.min_temperature.is_some_and(|min| temperature < min) {
+            return false;
+        }
+        if self.max_temperature.is_some_and(|max| temperature > max) {
+            return false;
+        }
+
+        true
+    }
+
+    /// Short description for listing, e.g. `models: gpt-4*, tags: review`.
+    pub fn describe(&self) -> Option<String> {
+        let mut result = Vec::new();
+        if let Some(models) = &self.models {
+            result.push(format!("models: {}", models.join(" ")));
+        }
+        if let Some(providers) = &self.providers {
+            result.push(format!("providers: {}", providers.join(" ")));
+        }
+        if let Some(tags) = &self.tags {
+            result.push(format!("tags: {}", tags.join(" ")));
+        }
+        if let Some(min) = self.min_temperature {
+            result.push(format!("temperature >= {min}"));
+        }
+        if let Some(max) = self.max_temperature {
+            result.push(format!("temperature <= {max}"));
+        }
+        if result.is_empty() {
+            None
+        } else {
+            Some(result.join(", "))
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PreMessage {
+    pub message: SavedMessage,
+    pub condition: PreMessageCondition,
+}
+
+impl From<SavedMessage> for PreMessage {
+    fn from(message: SavedMessage) -> Self {
+        PreMessage {
+            message,
+            condition: PreMessageCondition::default(),
+        }
+    }
+}
+
+/// Entry of pre message files after parsing.
+#[derive(Debug, Clone)]
+pub enum PreMessageEntry {
+    Message(PreMessage),
+    /// path or glob of other pre message files
+    Include(String),
+}
+
+/// Entry of YAML and JSON pre message files.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RawPreMessage {
+    pub system: Option<String>,
+    pub assistant: Option<String>,
+    pub user: Option<String>,
+    pub include: Option<String>,
+    pub models: Option<Vec<String>>,
+    pub providers: Option<Vec<String>>,
+    pub tags: Option<Vec<String>>,
+    pub min_temperature: Option<f32>,
+    pub max_temperature: Option<f32>,
+}
+
+impl TryFrom<RawPreMessage> for PreMessageEntry {
+    type Error = String;
+
+    fn try_from(value: RawPreMessage) -> Result<Self, Self::Error> {
+        let condition = PreMessageCondition {
+            models: value.models,
+            providers: value.providers,
+            tags: value.tags,
+            min_temperature: value.min_temperature,
+            max_temperature: value.max_temperature,
+        };
+
+        let message = if let Some(text) = value.system {
+            SavedMessage::System(text)
+        } else if let Some(text) = value.assistant {
+            SavedMessage::Assistant(text)
+        } else if let Some(text) = value.user {
+            SavedMessage::User(text)
+        } else if let Some(path) = value.include {
+            if condition.describe().is_some() {
+                return Err("conditions can not be set to include".to_string());
+            }
+            return Ok(Self::Include(path));
+        } else {
+            return Err("no message".to_string());
+        };
+
+        Ok(Self::Message(PreMessage { message, condition }))
+    }
+}
+
+/// Front matter of Markdown pre message files, between `---` lines at the top of the file.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MarkdownFrontMatter {
+    /// system (default), assistant or user
+    pub role: Option<String>,
+    /// files are sent in ascending order, then by file name (default 0)
+    pub order: Option<i64>,
+    /// the file is skipped when false
+    pub enabled: Option<bool>,
+    pub models: Option<Vec<String>>,
+    pub providers: Option<Vec<String>>,
+    pub tags: Option<Vec<String>>,
+    pub min_temperature: Option<f32>,
+    pub max_temperature: Option<f32>,
+}
@@ -0,0 +1,261 @@
+use crate::models::config::Profile;
+use std::collections::BTreeMap;
+use toml::Value;
+
+#[derive(Debug, Clone, Copy)]
+pub enum FieldKind {
+    Str,
+    Bool,
+    Integer,
+    /// float within the range
+    Float(f32, f32),
+    /// comma separated strings
+    List,
+}
+
+impl FieldKind {
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            FieldKind::Str => "string",
+            FieldKind::Bool => "bool",
+            FieldKind::Integer => "integer",
+            FieldKind::Float(_, _) => "float",
+            FieldKind::List => "comma separated list",
+        }
+    }
+}
+
+/// A field of `Profile` editable by `config get/set/unset` and flags of `configure`.
+#[derive(Debug, Clone, Copy)]
+pub struct ProfileField {
+    pub key: &'static str,
+    pub kind: FieldKind,
+    /// masked when shown
+    pub is_secret: bool,
+    pub help: &'static str,
+}
+
+const fn field(key: &'static str, kind: FieldKind, help: &'static str) -> ProfileField {
+    ProfileField {
+        key,
+        kind,
+        is_secret: false,
+        help,
+    }
+}
+
+pub const PROFILE_FIELDS: &[ProfileField] = &[
+    field(
+        "source_profile",
+        FieldKind::Str,
+        "profile to inherit unset fields from",
+    ),
+    ProfileField {
+        key: "api_key",
+        kind: FieldKind::Str,
+        is_secret: true,
+        help: "OpenAI API key",
+    },
+    field(
+        "api_key_env",
+        FieldKind::Str,
+        "environment variable holding the API key",
+    ),
+    field(
+        "api_key_command",
+        FieldKind::Str,
+        "command printing the API key",
+    ),
+    field("api_key_file", FieldKind::Str, "file holding the API key"),
+    ProfileField {
+        key: "organization_id",
+        kind: FieldKind::Str,
+        is_secret: true,
+        help: "OpenAI Organization ID",
+    },
+    field("use_history", FieldKind::Bool, "send and save history"),
+    field(
+        "lenient_history",
+        FieldKind::Bool,
+        "skip broken history files",
+    ),
+    field("use_pre_messages", FieldKind::Bool, "send pre messages"),
+    field(
+        "inherit_pre_messages",
+        FieldKind::Bool,
+        "also send pre messages of source profiles",
+    ),
+    field(
+        "summary_threshold",
+        FieldKind::Integer,
+        "summarize history over tokens",
+    ),
+    field("summary_model", FieldKind::Str, "model used to summarize"),
+    field(
+        "summary_keep_turns",
+        FieldKind::Integer,
+        "recent turns kept without summarizing",
+    ),
+    field("model", FieldKind::Str, "ChatGPT model"),
+    field("temperature", FieldKind::Float(0.0, 2.0), "temperature"),
+    field("top_p", FieldKind::Float(0.0, 1.0), "top_p"),
+    field("max_tokens", FieldKind::Integer, "max_tokens"),
+    field(
+        "presence_penalty",
+        FieldKind::Float(-2.0, 2.0),
+        "presence_penalty",
+    ),
+    field(
+        "frequency_penalty",
+        FieldKind::Float(-2.0, 2.0),
+        "frequency_penalty",
+    ),
+    field("user", FieldKind::Str, "user sent to API"),
+    field(
+        "default_persona",
+        FieldKind::List,
+        "persona sets used when --persona is not given",
+    ),
+];
+
+/// prefix of keys addressing a template variable, e.g. `vars.lang`
+const VARS_PREFIX: &str = "vars.";
+
+pub fn find_field(key: &str) -> Option<&'static ProfileField> {
+    PROFILE_FIELDS.iter().find(|f| f.key == key)
+}
+
+fn unknown_key(key: &str) -> String {
+    let keys: Vec<&str> = PROFILE_FIELDS.iter().map(|f| f.key).collect();
+    match crate::migration::did_you_mean(key, keys.iter().copied()) {
+        Some(candidate) => format!("unknown key \"{key}\" (did you mean \"{candidate}\"?)"),
+        None => format!(
+            "unknown key \"{key}\" (available: {}, vars.NAME)",
+            keys.join(", ")
+        ),
+    }
+}
+
+/// Parse `text` as the value of `field`.
+pub fn parse_value(field: &ProfileField, text: &str) -> Result<Value, String> {
+    let invalid = || {
+        format!(
+            "invalid value \"{text}\" for {} (expected {})",
+            field.key,
+            field.kind.type_name()
+        )
+    };
+    Ok(match field.kind {
+        FieldKind::Str => Value::String(text.to_string()),
+        FieldKind::Bool => Value::Boolean(text.parse().map_err(|_| invalid())?),
+        FieldKind::Integer => Value::Integer(
+            text.parse::<u64>()
+                .map_err(|_| invalid())?
+                .try_into()
+                .map_err(|_| invalid())?,
+        ),
+        FieldKind::Float(min, max) => {
+            let value: f32 = text.parse().map_err(|_| invalid())?;
+            if !(min..=max).contains(&value) {
+                return Err(format!(
+                    "value {value} for {} is out of range ({min} to {max})",
+                    field.key
+                ));
+            }
+            Value::Float(value as f64)
+        }
+        FieldKind::List => Value::Array(
+            text.split(',')
+                .map(|s| s.trim())
+                .filter(|s| !s.is_empty())
+                .map(|s| Value::String(s.to_string()))
+                .collect(),
+        ),
+    })
+}
+
+fn format_value(field: &ProfileField, value: &Value) -> String {
+    match (field.kind, value) {
+        (_, Value::String(text)) => text.clone(),
+        (FieldKind::Float(_, _), Value::Float(value)) => (*value as f32).to_string(),
+        (_, Value::Array(values)) => values
+            .iter()
+            .map(|v| v.as_str().map(|s| s.to_string()).unwrap_or(v.to_string()))
+            .collect::<Vec<String>>()
+            .join(","),
+        (_, value) => value.to_string(),
+    }
+}
+
+/// Mask all but the last 5 characters, as `configure` shows the current API key.
+pub fn mask_secret(value: &str) -> String {
+    let count = value.chars().count();
+    let tail: String = value.chars().skip(count.saturating_sub(5)).collect();
+    format!("xxxxxxxxxx{tail}")
+}
+
+impl Profile {
+    pub fn to_table(&self) -> Result<toml::map::Map<String, Value>, String> {
+        match Value::try_from(self) {
+            Ok(Value::Table(table)) => Ok(table),
+            Ok(_) => Err("failed to serialize profile".to_string()),
+            Err(e) => Err(format!("failed to serialize profile: {e}")),
+        }
+    }
+
+    pub fn replace_with_table(
+        &mut self,
+        table: toml::map::Map<String, Value>,
+    ) -> Result<(), String> {
+        let profile: Profile = Value::Table(table)
+            .try_into()
+            .map_err(|e| format!("invalid profile: {e}"))?;
+        let resolved_sources = std::mem::take(&mut self.resolved_sources);
+        *self = profile;
+        self.resolved_sources = resolved_sources;
+        Ok(())
+    }
+
+    /// Value of `key` as text, or `None` when it is not set.
+    pub fn get_value(&self, key: &str) -> Result<Option<String>, String> {
+        if let Some(name) = key.strip_prefix(VARS_PREFIX) {
+            return Ok(self.vars.as_ref().and_then(|vars| vars.get(name).cloned()));
+        }
+        let field = find_field(key).ok_or_else(|| unknown_key(key))?;
+        Ok(self
+            .to_table()?
+            .get(field.key)
+            .map(|value| format_value(field, value)))
+    }
+
+    /// Parse `text` by the type of `key` and set it.
+    pub fn set_value(&mut self, key: &str, text: &str) -> Result<(), String> {
+        if let Some(name) = key.strip_prefix(VARS_PREFIX) {
+            crate::template::parse_var(&format!("{name}={text}"))?;
+            self.vars
+                .get_or_insert_with(BTreeMap::new)
+                .insert(name.to_string(), text.to_string());
+            return Ok(());
+        }
+        let field = find_field(key).ok_or_else(|| unknown_key(key))?;
+        let mut table = self.to_table()?;
+        table.insert(field.key.to_string(), parse_value(field, text)?);
+        self.replace_with_table(table)
+    }
+
+    pub fn unset_value(&mut self, key: &str) -> Result<(), String> {
+        if let Some(name) = key.strip_prefix(VARS_PREFIX) {
+            if let Some(vars) = self.vars.as_mut() {
+                vars.remove(name);
+                if vars.is_empty() {
+                    self.vars = None;
+                }
+            }
+            return Ok(());
+        }
+        let field = find_field(key).ok_or_else(|| unknown_key(key))?;
+        let mut table = self.to_table()?;
+        table.remove(field.key);
+        self.replace_with_table(table)
+    }
+}
@@ -0,0 +1,80 @@
+use crate::models::config::{Profile, API_KEY_SOURCES};
+use crate::models::profile_field::{find_field, PROFILE_FIELDS};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// `.kabeuchi.toml` of a project, layered over the selected profile.
+/// `history_backend` is global, as every history command has to read the same store.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProjectConfig {
+    /// fields of profile overriding those of the selected profile
+    #[serde(default)]
+    pub profile: toml::map::Map<String, toml::Value>,
+}
+
+/// fields which a project cannot set, as it may be shared with others
+const FORBIDDEN_PROJECT_FIELDS: &[&str] = &["source_profile", "organization_id"];
+
+impl ProjectConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        for key in self.profile.keys() {
+            if FORBIDDEN_PROJECT_FIELDS.contains(&key.as_str())
+                || API_KEY_SOURCES.contains(&key.as_str())
+            {
+                return Err(format!(
+                    "\"{key}\" cannot be set in project config; set it in the profile of the global config"
+                ));
+            }
+            if key != "vars" && find_field(key).is_none() {
+                let keys = PROFILE_FIELDS.iter().map(|f| f.key);
+                return Err(match crate::migration::did_you_mean(key, keys) {
+                    Some(candidate) => format!(
+                        "unknown key \"profile.{key}\" in project config (did you mean \"profile.{candidate}\"?)"
+                    ),
+                    None => format!("unknown key \"profile.{key}\" in project config"),
+                });
+            }
+        }
+        self.to_profile("").map(|_| ())
+    }
+
+    /// Fields of `[profile]` as a profile named `name`.
+    pub fn to_profile(&self, name: &str) -> Result<Profile, String> {
+        let mut table = self.profile.clone();
+        table.insert("name".to_string(), toml::Value::String(name.to_string()));
+        toml::Value::Table(table)
+            .try_into()
+            .map_err(|e| format!("invalid profile in project config: {e}"))
+    }
+}
+
+/// Project config files which the user allowed to be read, with the text allowed.
+/// A changed file has to be trusted again.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct TrustedProjects {
+    #[serde(default)]
+    pub project: Vec<TrustedProject>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct TrustedProject {
+    pub path: PathBuf,
+    pub text: String,
+}
+
+impl TrustedProjects {
+    pub fn is_trusted(&self, path: &PathBuf, text: &str) -> bool {
+        self.project
+            .iter()
+            .any(|p| &p.path == path && p.text == text)
+    }
+
+    pub fn trust(&mut self, path: &PathBuf, text: &str) {
+        self.project.retain(|p| &p.path != path);
+        self.project.push(TrustedProject {
+            path: path.clone(),
+            text: text.to_string(),
+        });
+    }
+}
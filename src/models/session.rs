@@ -0,0 +1,39 @@
+use serde::{Deserialize, Serialize};
+
+pub const MAIN_SESSION: &str = "main";
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Session {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parent: Option<String>,
+    pub forked_at: usize,
+}
+
+impl Session {
+    pub fn get_parent_name(&self) -> &str {
+        match &self.parent {
+            Some(parent) => parent,
+            None => MAIN_SESSION,
+        }
+    }
+}
+
+pub fn validate_session_name(name: &str) -> Result<(), String> {
+    if name.is_empty() {
+        return Err("session name is empty".to_string());
+    }
+    if name == MAIN_SESSION {
+        return Err(format!("session name \"{MAIN_SESSION}\" is reserved"));
+    }
+    if name.starts_with('.')
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+    {
+        return Err(format!(
+            "invalid session name \"{name}\" (allowed: alphanumeric, '-', '_', '.')"
+        ));
+    }
+    Ok(())
+}
@@ -0,0 +1,9 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct HistorySummary {
+    pub content: String,
+    /// id of the last turn condensed into `content`
+    pub until: String,
+    pub model: String,
+}
@@ -0,0 +1,31 @@
+use crate::models::api::{FunctionDefinition, Tool};
+use serde::Deserialize;
+
+/// A tool declared in a file of the tools dir of a profile.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ToolDefinition {
+    pub name: String,
+    pub description: Option<String>,
+    /// JSON schema of the arguments; no arguments when unset
+    pub parameters: Option<serde_json::Value>,
+    /// Command run with the arguments (JSON) as stdin, whose stdout is the result.
+    /// The result is asked to the user when unset.
+    pub command: Option<Vec<String>>,
+}
+
+impl ToolDefinition {
+    pub fn to_tool(&self) -> Tool {
+        Tool {
+            kind: "function".to_string(),
+            function: FunctionDefinition {
+                name: self.name.clone(),
+                description: self.description.clone(),
+                parameters: self
+                    .parameters
+                    .clone()
+                    .unwrap_or(serde_json::json!({"type": "object", "properties": {}})),
+            },
+        }
+    }
+}
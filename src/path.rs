@@ -1,14 +1,63 @@
 use clap::crate_name;
 use dirs::home_dir;
 use std::path::PathBuf;
+use std::sync::OnceLock;
 
+/// path of the config file given by the global `--config` flag
+static CONFIG_FILE: OnceLock<PathBuf> = OnceLock::new();
+
+pub fn set_path_config_file(path: PathBuf) {
+    let _ = CONFIG_FILE.set(path);
+}
+
+/// `$XDG_*_HOME/kabeuchi`, or `~/<default>/kabeuchi` when the variable is unset or not absolute.
+/// Everything is put in `KABEUCHI_CONFIG_DIR` when it is set, as the legacy layout.
+fn get_path_base_dir(xdg_name: &str, default: &str) -> Result<PathBuf, String> {
+    if let Some(dir) = crate::env::get(crate::env::KABEUCHI_CONFIG_DIR) {
+        return Ok(PathBuf::from(dir));
+    }
+    let base = match crate::env::get(xdg_name)
+        .map(PathBuf::from)
+        .filter(|p| p.is_absolute())
+    {
+        Some(base) => base,
+        None => home_dir()
+            .map(|h| h.join(default))
+            .ok_or_else(|| "failed to resolve Home directory".to_string())?,
+    };
+    Ok(base.join(crate_name!()))
+}
+
+/// config file, pre messages and personas
 pub fn get_path_config_dir() -> Result<PathBuf, String> {
-    home_dir()
-        .map(|h| h.join(".config").join(crate_name!()))
-        .ok_or_else(|| "failed to resolve Home directory".to_string())
+    get_path_base_dir(crate::env::XDG_CONFIG_HOME, ".config")
+}
+
+/// history and usage
+pub fn get_path_data_dir() -> Result<PathBuf, String> {
+    get_path_base_dir(crate::env::XDG_DATA_HOME, ".local/share")
+}
+
+/// trusted projects and quarantined files
+pub fn get_path_state_dir() -> Result<PathBuf, String> {
+    get_path_base_dir(crate::env::XDG_STATE_HOME, ".local/state")
+}
+
+/// locks and the message being edited
+pub fn get_path_cache_dir() -> Result<PathBuf, String> {
+    if crate::env::get(crate::env::KABEUCHI_CONFIG_DIR).is_some() {
+        return get_path_config_dir().map(|p| p.join("cache"));
+    }
+    get_path_base_dir(crate::env::XDG_CACHE_HOME, ".cache")
 }
 
 pub fn get_path_config_file() -> Result<PathBuf, String> {
+    if let Some(path) = CONFIG_FILE.get() {
+        return Ok(path.clone());
+    }
+    if let Some(path) = crate::env::get(crate::env::KABEUCHI_CONFIG) {
+        return Ok(PathBuf::from(path));
+    }
     get_path_config_dir().map(|h| h.join("config.toml"))
 }
 
@@ -20,16 +69,86 @@ pub fn get_path_profile_pre_messages_dir(profile_name: &str) -> Result<PathBuf,
     get_path_pre_messages_dir().map(|p| p.join(profile_name))
 }
 
+pub fn get_path_tools_dir() -> Result<PathBuf, String> {
+    get_path_config_dir().map(|p| p.join("tools"))
+}
+
+pub fn get_path_profile_tools_dir(profile_name: &str) -> Result<PathBuf, String> {
+    get_path_tools_dir().map(|p| p.join(profile_name))
+}
+
+pub fn get_path_personas_dir() -> Result<PathBuf, String> {
+    get_path_config_dir().map(|p| p.join("personas"))
+}
+
+/// A persona is a directory right under the personas dir, so its name must not be a path.
+pub fn get_path_persona_dir(persona_name: &str) -> Result<PathBuf, String> {
+    if persona_name.is_empty() || persona_name.contains(['/', '\\']) || persona_name.contains("..")
+    {
+        return Err(format!(
+            "invalid persona name \"{persona_name}\" (path separators and \"..\" are not allowed)"
+        ));
+    }
+    get_path_personas_dir().map(|p| p.join(persona_name))
+}
+
+pub fn get_path_trusted_projects_file() -> Result<PathBuf, String> {
+    get_path_state_dir().map(|p| p.join("trusted_projects.toml"))
+}
+
 pub fn get_path_history_dir() -> Result<PathBuf, String> {
-    get_path_config_dir().map(|p| p.join("history"))
+    get_path_data_dir().map(|p| p.join("history"))
 }
 
 pub fn get_path_profile_history_dir(profile_name: &str) -> Result<PathBuf, String> {
     get_path_history_dir().map(|p| p.join(profile_name))
 }
 
-pub fn get_path_cache_dir() -> Result<PathBuf, String> {
-    get_path_config_dir().map(|p| p.join("cache"))
+pub fn get_path_profile_sessions_dir(profile_name: &str) -> Result<PathBuf, String> {
+    get_path_profile_history_dir(profile_name).map(|p| p.join("sessions"))
+}
+
+pub fn get_path_session_history_dir(
+    profile_name: &str,
+    session_name: &str,
+) -> Result<PathBuf, String> {
+    get_path_profile_sessions_dir(profile_name).map(|p| p.join(session_name))
+}
+
+pub fn get_path_history_db_file() -> Result<PathBuf, String> {
+    get_path_data_dir().map(|p| p.join("history.sqlite3"))
+}
+
+pub fn get_path_usage_dir() -> Result<PathBuf, String> {
+    get_path_data_dir().map(|p| p.join("usage"))
+}
+
+pub fn get_path_profile_usage_file(profile_name: &str) -> Result<PathBuf, String> {
+    get_path_usage_dir().map(|p| p.join(format!("{profile_name}.jsonl")))
+}
+
+pub fn get_path_quarantine_dir() -> Result<PathBuf, String> {
+    get_path_state_dir().map(|p| p.join("quarantine"))
+}
+
+pub fn get_path_models_cache_dir() -> Result<PathBuf, String> {
+    get_path_cache_dir().map(|p| p.join("models"))
+}
+
+pub fn get_path_profile_models_cache_file(profile_name: &str) -> Result<PathBuf, String> {
+    get_path_models_cache_dir().map(|p| p.join(format!("{profile_name}.json")))
+}
+
+fn get_path_lock_dir() -> Result<PathBuf, String> {
+    get_path_cache_dir().map(|p| p.join("lock"))
+}
+
+pub fn get_path_config_lock_file() -> Result<PathBuf, String> {
+    get_path_lock_dir().map(|p| p.join("config.lock"))
+}
+
+pub fn get_path_profile_lock_file(profile_name: &str) -> Result<PathBuf, String> {
+    get_path_lock_dir().map(|p| p.join("profiles").join(format!("{profile_name}.lock")))
 }
 
 fn get_path_editting_message_dir() -> Result<PathBuf, String> {
@@ -54,7 +173,7 @@ pub fn get_files_in_dir(path: &PathBuf) -> Result<Vec<PathBuf>, String> {
         let entry =
             entry.map_err(|e| format!("failed to parse result of reading directory: {e}"))?;
         let path_file = entry.path();
-        if !path_file.is_file() {
+        if !path_file.is_file() || entry.file_name().to_string_lossy().starts_with('.') {
             continue;
         }
         result.push(path_file.clone());
@@ -62,3 +181,91 @@ pub fn get_files_in_dir(path: &PathBuf) -> Result<Vec<PathBuf>, String> {
     result.sort();
     Ok(result)
 }
+
+/// Files of the legacy layout, where everything was in `~/.config/kabeuchi`,
+/// with the directory each one is moved to.
+/// Files of a group are moved together.
+fn get_legacy_files() -> Result<Vec<(Vec<&'static str>, PathBuf)>, String> {
+    let config_dir = get_path_config_dir()?;
+    let data_dir = get_path_data_dir()?;
+    let state_dir = get_path_state_dir()?;
+    Ok(vec![
+        (vec!["config.toml"], config_dir.clone()),
+        (vec!["pre_messages"], config_dir.clone()),
+        (vec!["tools"], config_dir.clone()),
+        (vec!["personas"], config_dir),
+        (vec!["history"], data_dir.clone()),
+        // the journal files belong to the database, and must not be put next to another one
+        (
+            vec![
+                "history.sqlite3",
+                "history.sqlite3-journal",
+                "history.sqlite3-wal",
+                "history.sqlite3-shm",
+            ],
+            data_dir.clone(),
+        ),
+        (vec!["usage"], data_dir),
+        (vec!["quarantine"], state_dir.clone()),
+        (vec!["trusted_projects.toml"], state_dir),
+    ])
+}
+
+/// Move files of the legacy layout to the XDG base directories.
+/// Files already existing in the new place are left as they are, with a warning.
+pub fn migrate_legacy_layout() -> Result<(), String> {
+    if crate::env::get(crate::env::KABEUCHI_CONFIG_DIR).is_some() {
+        return Ok(());
+    }
+    let Some(legacy_dir) = home_dir().map(|h| h.join(".config").join(crate_name!())) else {
+        return Ok(());
+    };
+
+    // (files to move, a target which already exists) of each group having legacy files
+    let scan = |files: &[(Vec<&str>, PathBuf)]| {
+        files
+            .iter()
+            .map(|(names, dir)| {
+                let paths: Vec<(PathBuf, PathBuf)> = names
+                    .iter()
+                    .map(|name| (legacy_dir.join(name), dir.join(name)))
+                    .filter(|(from, to)| from != to)
+                    .collect();
+                let existing = paths
+                    .iter()
+                    .map(|(_, to)| to)
+                    .find(|to| to.exists())
+                    .cloned();
+                let moves: Vec<_> = paths
+                    .into_iter()
+                    .filter(|(from, _)| from.exists())
+                    .collect();
+                (moves, existing)
+            })
+            .filter(|(moves, _)| !moves.is_empty())
+            .collect::<Vec<_>>()
+    };
+    let files = get_legacy_files()?;
+    if scan(&files).is_empty() {
+        return Ok(());
+    }
+
+    let _lock = crate::fs::lock_exclusive(&get_path_lock_dir()?.join("layout.lock"))?;
+    for (moves, existing) in scan(&files) {
+        if let Some(existing) = existing {
+            for (from, _) in moves {
+                eprintln!(
+                    "warning: legacy {} is left as it is, as {} already exists",
+                    from.display(),
+                    existing.display()
+                );
+            }
+            continue;
+        }
+        for (from, to) in moves {
+            crate::fs::move_path(&from, &to)?;
+            eprintln!("moved {} to {}", from.display(), to.display());
+        }
+    }
+    Ok(())
+}
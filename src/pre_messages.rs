@@ -0,0 +1,461 @@
+use crate::models::config::Profile;
+use crate::models::messages::SavedMessage;
+use crate::models::pre_message::{
+    MarkdownFrontMatter, PreMessage, PreMessageCondition, PreMessageEntry, RawPreMessage,
+};
+use crate::path::{
+    get_files_in_dir, get_path_config_dir, get_path_persona_dir, get_path_profile_pre_messages_dir,
+};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Per-call options for assembling pre messages.
+#[derive(Debug, Clone, Default)]
+pub struct PreMessageOptions {
+    /// template variables given by `--var`, which take precedence over `vars` of profile
+    pub vars: BTreeMap<String, String>,
+    /// persona sets given by `--persona`, which replace `default_persona` of profile
+    pub personas: Vec<String>,
+    /// tags given by `--tag`, which select pre messages having `tags` condition
+    pub tags: Vec<String>,
+}
+
+pub fn get_pre_messages(
+    profile: &Profile,
+    options: &PreMessageOptions,
+) -> Result<Vec<SavedMessage>, String> {
+    // directories with the root which `include` of their files must stay in
+    let mut directories = Vec::new();
+    let root = get_path_config_dir()?;
+
+    if profile.get_use_pre_messages() {
+        if profile.get_inherit_pre_messages() {
+            for name in profile.resolved_sources.iter().rev() {
+                directories.push((get_path_profile_pre_messages_dir(name)?, root.clone()));
+            }
+        }
+        directories.push((
+            get_path_profile_pre_messages_dir(&profile.name)?,
+            root.clone(),
+        ));
+        // files of a project only include files in `.kabeuchi` of the project
+        if let Some(directory) = &profile.project_pre_messages_dir {
+            let project_root = directory.parent().unwrap_or(directory).to_path_buf();
+            directories.push((directory.clone(), project_root));
+        }
+    }
+
+    if !profile.get_use_pre_messages() && !options.personas.is_empty() {
+        return Err(format!(
+            "--persona is given, but use_pre_messages of profile \"{}\" is false",
+            profile.name
+        ));
+    }
+    let personas = if !profile.get_use_pre_messages() {
+        Vec::new()
+    } else if options.personas.is_empty() {
+        profile.default_persona.clone().unwrap_or_default()
+    } else {
+        options.personas.clone()
+    };
+    for persona in personas {
+        let directory = get_path_persona_dir(&persona)?;
+        if !directory.is_dir() {
+            return Err(format!(
+                "Persona \"{persona}\" is not exists: path={}",
+                directory.display()
+            ));
+        }
+        directories.push((directory, root.clone()));
+    }
+
+    let mut vars = profile.vars.clone().unwrap_or_default();
+    vars.extend(options.vars.clone());
+
+    let mut result = Vec::new();
+
+    for (directory, root) in directories {
+        let mut files = Vec::new();
+        for path in get_files_in_dir(&directory)? {
+            let file = load_pre_message_file(&path, &root)?;
+            files.push((path, file));
+        }
+        // stable sort keeps the order of file names within the same `order`
+        files.sort_by_key(|(_, file)| file.order);
+
+        for (path, file) in files {
+            for pre_message in file.messages {
+                if !pre_message.condition.matches(profile, &options.tags) {
+                    continue;
+                }
+                result.push(
+                    render_message(pre_message.message, &vars)
+                        .map_err(|e| format!("{e}: path={}", path.display()))?,
+                );
+            }
+        }
+    }
+
+    Ok(result)
+}
+
+fn render_message(
+    message: SavedMessage,
+    vars: &BTreeMap<String, String>,
+) -> Result<SavedMessage, String> {
+    Ok(match message {
+        SavedMessage::System(text) => SavedMessage::System(crate::template::render(&text, vars)?),
+        SavedMessage::Assistant(text) => {
+            SavedMessage::Assistant(crate::template::render(&text, vars)?)
+        }
+        SavedMessage::User(text) => SavedMessage::User(crate::template::render(&text, vars)?),
+    })
+}
+
+/// Messages of a pre message file with `include` entries expanded.
+#[derive(Debug, Clone, Default)]
+pub struct PreMessageFile {
+    /// `order` of the front matter of Markdown files, 0 otherwise
+    pub order: i64,
+    pub messages: Vec<PreMessage>,
+}
+
+/// Load a pre message file, expanding `include` entries recursively.
+/// `include` is resolved relative to the including file, and must stay in `root`,
+/// usually the config dir.
+pub fn load_pre_message_file(path: &PathBuf, root: &Path) -> Result<PreMessageFile, String> {
+    let mut result = PreMessageFile::default();
+    result.order = expand_file(path, root, &mut Vec::new(), &mut result.messages)?;
+    Ok(result)
+}
+
+/// Returns the `order` of the file.
+fn expand_file(
+    path: &PathBuf,
+    root: &Path,
+    stack: &mut Vec<PathBuf>,
+    result: &mut Vec<PreMessage>,
+) -> Result<i64, String> {
+    let canonical = path.canonicalize().map_err(|e| {
+        format!(
+            "failed to resolve pre message file: path={}, err={}",
+            path.display(),
+            e
+        )
+    })?;
+    if stack.contains(&canonical) {
+        let chain: Vec<String> = stack
+            .iter()
+            .chain(std::iter::once(&canonical))
+            .map(|p| p.display().to_string())
+            .collect();
+        return Err(format!("include cycle detected: {}", chain.join(" -> ")));
+    }
+
+    stack.push(canonical);
+    let (order, entries) = parse_pre_message_file(path)?;
+    for entry in entries {
+        match entry {
+            PreMessageEntry::Message(message) => result.push(message),
+            PreMessageEntry::Include(pattern) => {
+                for included in resolve_include(path, root, &pattern)? {
+                    expand_file(&included, root, stack, result)?;
+                }
+            }
+        }
+    }
+    stack.pop();
+
+    Ok(order)
+}
+
+/// Resolve `include` relative to the directory of `path`, rejecting files outside of `root`.
+/// Patterns containing `*`, `?` or `[` are expanded as globs in sorted order.
+fn resolve_include(path: &Path, root: &Path, pattern: &str) -> Result<Vec<PathBuf>, String> {
+    let target = path.parent().unwrap_or(Path::new("")).join(pattern);
+    let check_inside_root = |included: PathBuf| -> Result<PathBuf, String> {
+        let inside = match (included.canonicalize(), root.canonicalize()) {
+            (Ok(included), Ok(root)) => included.starts_with(root),
+            _ => false,
+        };
+        if !inside {
+            return Err(format!(
+                "included file is outside of {}: include={pattern}, path={}",
+                root.display(),
+                path.display()
+            ));
+        }
+        Ok(included)
+    };
+
+    if !pattern.contains(['*', '?', '[']) {
+        if !target.is_file() {
+            return Err(format!(
+                "included file is not exists: include={pattern}, path={}",
+                path.display()
+            ));
+        }
+        return Ok(vec![check_inside_root(target)?]);
+    }
+
+    let glob_pattern = target.to_string_lossy().to_string();
+    let mut result = Vec::new();
+    for entry in glob::glob(&glob_pattern)
+        .map_err(|e| format!("invalid include pattern: include={pattern}, err={e}"))?
+    {
+        let entry = entry.map_err(|e| format!("failed to read included file: {e}"))?;
+        if entry.is_file() {
+            result.push(check_inside_root(entry)?);
+        }
+    }
+    if result.is_empty() {
+        return Err(format!(
+            "no file matched include pattern: include={pattern}, path={}",
+            path.display()
+        ));
+    }
+    result.sort();
+    Ok(result)
+}
+
+fn parse_pre_message_file(path: &PathBuf) -> Result<(i64, Vec<PreMessageEntry>), String> {
+    let text = crate::fs::load_text(path)?;
+    if let Some(extension) = path.extension() {
+        let extension = extension
+            .to_str()
+            .ok_or("failed to get string of extname".to_string())?
+            .to_lowercase();
+        if extension == "json" || extension == "yaml" || extension == "yml" {
+            let raw_pre_messages: Vec<RawPreMessage> = if extension == "json" {
+                serde_json::from_str(&text).map_err(|e| {
+                    format!(
+                        "failed to deserialize json: path={}, err={}",
+                        path.display(),
+                        e
+                    )
+                })?
+            } else {
+                serde_yaml::from_str(&text).map_err(|e| {
+                    format!(
+                        "failed to deserialize yaml: path={}, err={}",
+                        path.display(),
+                        e
+                    )
+                })?
+            };
+            let mut result = Vec::new();
+            for (i, message) in raw_pre_messages.into_iter().enumerate() {
+                result.push(
+                    message
+                        .try_into()
+                        .map_err(|e| format!("{e}: path={}, entry={}", path.display(), i + 1))?,
+                );
+            }
+            return Ok((0, result));
+        } else if extension == "md" {
+            return parse_markdown(path, &text);
+        }
+    }
+    let message = match get_role_from_file_name(path)? {
+        Some("system") => SavedMessage::System(text),
+        Some("assistant") => SavedMessage::Assistant(text),
+        Some("user") => SavedMessage::User(text),
+        _ => return Ok((0, Vec::new())),
+    };
+    Ok((0, vec![PreMessageEntry::Message(message.into())]))
+}
+
+/// Role of a plain text file, given by `system`, `assistant` or `user` in the file name.
+fn get_role_from_file_name(path: &Path) -> Result<Option<&'static str>, String> {
+    let file_name = path
+        .file_name()
+        .ok_or("failed to get filename".to_string())?
+        .to_str()
+        .ok_or("failed to get string of filename".to_string())?
+        .to_lowercase();
+
+    Ok(["system", "assistant", "user"]
+        .into_iter()
+        .find(|role| file_name.contains(role)))
+}
+
+/// Parse a Markdown file: optional front matter between `---` lines, then the body as content.
+fn parse_markdown(path: &Path, text: &str) -> Result<(i64, Vec<PreMessageEntry>), String> {
+    let (front_matter, body) = split_front_matter(text);
+    let front_matter: MarkdownFrontMatter = match front_matter {
+        Some(front_matter) if !front_matter.trim().is_empty() => serde_yaml::from_str(front_matter)
+            .map_err(|e| {
+                format!(
+                    "failed to deserialize front matter: path={}, err={}",
+                    path.display(),
+                    e
+                )
+            })?,
+        _ => MarkdownFrontMatter::default(),
+    };
+    let order = front_matter.order.unwrap_or(0);
+
+    if !front_matter.enabled.unwrap_or(true) {
+        return Ok((order, Vec::new()));
+    }
+
+    let body = body.trim_start_matches(['\r', '\n']).trim_end().to_string();
+    // without `role`, the file name decides it as for plain text files, and files such as
+    // README.md with neither are not pre messages
+    let role = match front_matter.role.as_deref() {
+        Some(role) => role,
+        None => match get_role_from_file_name(path)? {
+            Some(role) => role,
+            None => return Ok((order, Vec::new())),
+        },
+    };
+    let message = match role {
+        "system" => SavedMessage::System(body),
+        "assistant" => SavedMessage::Assistant(body),
+        "user" => SavedMessage::User(body),
+        role => {
+            return Err(format!(
+            "invalid role \"{role}\" in front matter (expected system, assistant or user): path={}",
+            path.display()
+        ))
+        }
+    };
+
+    Ok((
+        order,
+        vec![PreMessageEntry::Message(PreMessage {
+            message,
+            condition: PreMessageCondition {
+                models: front_matter.models,
+                providers: front_matter.providers,
+                tags: front_matter.tags,
+                min_temperature: front_matter.min_temperature,
+                max_temperature: front_matter.max_temperature,
+            },
+        })],
+    ))
+}
+
+/// Split `---\n<front matter>\n---\n<body>`. Text without the opening `---` is all body.
+fn split_front_matter(text: &str) -> (Option<&str>, &str) {
+    let Some(rest) = text
+        .strip_prefix("---\n")
+        .or_else(|| text.strip_prefix("---\r\n"))
+    else {
+        return (None, text);
+    };
+
+    let mut offset = 0;
+    for line in rest.split_inclusive('\n') {
+        if line.trim_end() == "---" {
+            return (Some(&rest[..offset]), &rest[offset + line.len()..]);
+        }
+        offset += line.len();
+    }
+    (None, text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// config dir with `pre_messages/<profile>/` and the given files under it
+    fn config_dir(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("kabeuchi-test-{}-{name}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        for (path, text) in files {
+            let path = dir.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, text).unwrap();
+        }
+        dir
+    }
+
+    fn texts(file: &PreMessageFile) -> Vec<String> {
+        file.messages
+            .iter()
+            .map(|m| match &m.message {
+                SavedMessage::System(text) => text.clone(),
+                SavedMessage::Assistant(text) => text.clone(),
+                SavedMessage::User(text) => text.clone(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn include_is_relative_to_including_file() {
+        let dir = config_dir(
+            "include-relative",
+            &[
+                (
+                    "pre_messages/default/review.yaml",
+                    "- include: ../shared/standards.yaml\n- user: review this\n",
+                ),
+                (
+                    "pre_messages/shared/standards.yaml",
+                    "- system: follow the standards\n",
+                ),
+            ],
+        );
+        let path = dir.join("pre_messages/default/review.yaml");
+        let file = load_pre_message_file(&path, &dir).unwrap();
+        assert_eq!(texts(&file), ["follow the standards", "review this"]);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn include_outside_of_root_is_rejected() {
+        let dir = config_dir(
+            "include-outside",
+            &[
+                (
+                    "config/pre_messages/default/a.yaml",
+                    "- include: ../../../secret.yaml\n",
+                ),
+                ("secret.yaml", "- system: secret\n"),
+            ],
+        );
+        let path = dir.join("config/pre_messages/default/a.yaml");
+        let err = load_pre_message_file(&path, &dir.join("config")).unwrap_err();
+        assert!(err.contains("included file is outside of"), "{err}");
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn markdown_role_is_taken_from_front_matter_or_file_name() {
+        let dir = config_dir(
+            "markdown-role",
+            &[
+                ("rules.md", "---\nrole: user\n---\nreview this\n"),
+                ("010_system.md", "be concise\n"),
+                ("README.md", "# notes for myself\n"),
+            ],
+        );
+        let load = |name: &str| load_pre_message_file(&dir.join(name), &dir).unwrap();
+        assert!(matches!(
+            load("rules.md").messages[0].message,
+            SavedMessage::User(_)
+        ));
+        assert!(matches!(
+            load("010_system.md").messages[0].message,
+            SavedMessage::System(_)
+        ));
+        assert!(load("README.md").messages.is_empty());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn include_cycle_is_rejected() {
+        let dir = config_dir(
+            "include-cycle",
+            &[
+                ("pre_messages/default/a.yaml", "- include: b.yaml\n"),
+                ("pre_messages/default/b.yaml", "- include: a.yaml\n"),
+            ],
+        );
+        let path = dir.join("pre_messages/default/a.yaml");
+        let err = load_pre_message_file(&path, &dir).unwrap_err();
+        assert!(err.contains("include cycle detected"), "{err}");
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,117 @@
+use crate::models::project::{ProjectConfig, TrustedProjects};
+use std::io::IsTerminal;
+use std::path::PathBuf;
+
+pub const PROJECT_CONFIG_FILE: &str = ".kabeuchi.toml";
+pub const PROJECT_DIR: &str = ".kabeuchi";
+
+/// A project found by walking up from the current directory.
+#[derive(Debug, Clone)]
+pub struct Project {
+    /// directory containing `.kabeuchi.toml`
+    pub root: PathBuf,
+    pub config: ProjectConfig,
+}
+
+impl Project {
+    pub fn get_path_config_file(&self) -> PathBuf {
+        self.root.join(PROJECT_CONFIG_FILE)
+    }
+
+    pub fn get_path_pre_messages_dir(&self) -> PathBuf {
+        self.root.join(PROJECT_DIR).join("pre_messages")
+    }
+}
+
+/// Path of the nearest `.kabeuchi.toml` from the current directory.
+pub fn find_project_config_file() -> Result<Option<PathBuf>, String> {
+    let current =
+        std::env::current_dir().map_err(|e| format!("failed to get current directory: {e}"))?;
+    Ok(current
+        .ancestors()
+        .map(|dir| dir.join(PROJECT_CONFIG_FILE))
+        .find(|path| path.is_file()))
+}
+
+/// Load the nearest project. A project config seen for the first time or changed since
+/// trusted is used only after the user trusts it, otherwise it is ignored.
+pub fn load_project() -> Result<Option<Project>, String> {
+    if crate::env::parse(crate::env::KABEUCHI_NO_PROJECT)?.unwrap_or(false) {
+        return Ok(None);
+    }
+    let Some(path) = find_project_config_file()? else {
+        return Ok(None);
+    };
+
+    let text = crate::fs::load_text(&path)?;
+    let config: ProjectConfig = toml::from_str(&text).map_err(|e| {
+        format!(
+            "failed to deserialize project config: path={}, err={}",
+            path.display(),
+            e
+        )
+    })?;
+    config
+        .validate()
+        .map_err(|e| format!("{e}: path={}", path.display()))?;
+    if !confirm_trust(&path, &text)? {
+        return Ok(None);
+    }
+
+    Ok(Some(Project {
+        root: path.parent().unwrap_or(&path).to_path_buf(),
+        config,
+    }))
+}
+
+fn load_trusted_projects() -> Result<TrustedProjects, String> {
+    let path = crate::path::get_path_trusted_projects_file()?;
+    if !path.exists() {
+        return Ok(TrustedProjects::default());
+    }
+    let text = crate::fs::load_text(&path)?;
+    toml::from_str(&text).map_err(|e| {
+        format!(
+            "failed to deserialize trusted projects: path={}, err={}",
+            path.display(),
+            e
+        )
+    })
+}
+
+fn confirm_trust(path: &PathBuf, text: &str) -> Result<bool, String> {
+    let mut trusted = load_trusted_projects()?;
+    if trusted.is_trusted(path, text) {
+        return Ok(true);
+    }
+
+    if !std::io::stdin().is_terminal() {
+        eprintln!(
+            "warning: project config is not trusted and ignored: path={}\n         run kabeuchi in a terminal once to trust it",
+            path.display()
+        );
+        return Ok(false);
+    }
+
+    // the dialog goes to stderr so that it is not mixed into the output of `kabeuchi prompt`
+    eprintln!(
+        "found new or changed project config: path={}",
+        path.display()
+    );
+    eprintln!("----\n{}\n----", text.trim_end());
+    eprintln!(
+        "a trusted project can change the model and send files of the project as pre messages."
+    );
+    if !crate::input::get_confirm_on_stderr("trust this project config? [y/N]: ", false)?
+        .unwrap_or(false)
+    {
+        eprintln!("project config is ignored");
+        return Ok(false);
+    }
+
+    trusted.trust(path, text);
+    let text = toml::to_string_pretty(&trusted)
+        .map_err(|e| format!("failed to serialize trusted projects: {e}"))?;
+    crate::fs::save_text(&crate::path::get_path_trusted_projects_file()?, &text)?;
+    Ok(true)
+}
@@ -0,0 +1,66 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Run `command` with the shell and return its stdout without the trailing newline.
+pub fn read_command(command: &str) -> Result<String, String> {
+    let output = if cfg!(windows) {
+        Command::new("cmd").args(["/C", command]).output()
+    } else {
+        Command::new("sh").args(["-c", command]).output()
+    }
+    .map_err(|e| format!("failed to run api_key_command: command={command}, err={e}"))?;
+
+    if !output.status.success() {
+        return Err(format!(
+            "api_key_command failed: command={command}, status={}, stderr={}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    let stdout = String::from_utf8(output.stdout)
+        .map_err(|e| format!("output of api_key_command is not UTF-8: {e}"))?;
+    Ok(stdout.trim().to_string())
+}
+
+/// Read the first line of `path`, refusing files which others can read.
+pub fn read_file(path: &Path) -> Result<String, String> {
+    let path = expand_home(path);
+    check_not_world_readable(&path)?;
+    let text = crate::fs::load_text(&path)?;
+    Ok(text.lines().next().unwrap_or_default().trim().to_string())
+}
+
+fn expand_home(path: &Path) -> PathBuf {
+    match (path.strip_prefix("~"), dirs::home_dir()) {
+        (Ok(rest), Some(home)) => home.join(rest),
+        _ => path.to_path_buf(),
+    }
+}
+
+#[cfg(unix)]
+fn check_not_world_readable(path: &Path) -> Result<(), String> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let metadata = std::fs::metadata(path).map_err(|e| {
+        format!(
+            "failed to read api_key_file: path={}, err={}",
+            path.display(),
+            e
+        )
+    })?;
+    let mode = metadata.permissions().mode();
+    if mode & 0o004 != 0 {
+        return Err(format!(
+            "api_key_file is readable by others (mode {:o}); run `chmod 600 {}`",
+            mode & 0o777,
+            path.display()
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn check_not_world_readable(_path: &Path) -> Result<(), String> {
+    Ok(())
+}
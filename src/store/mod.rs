@@ -0,0 +1,22 @@
+mod sqlite;
+mod yaml;
+
+pub use sqlite::SqliteHistoryStore;
+pub use yaml::{load_turn_file, YamlHistoryStore};
+
+use crate::models::config::{Config, HistoryBackend};
+use crate::traits::history_store::HistoryStore;
+
+pub fn open_store(backend: &HistoryBackend) -> Result<Box<dyn HistoryStore>, String> {
+    match backend {
+        HistoryBackend::Yaml => Ok(Box::new(YamlHistoryStore)),
+        HistoryBackend::Sqlite => Ok(Box::new(SqliteHistoryStore::open(
+            &crate::path::get_path_history_db_file()?,
+        )?)),
+    }
+}
+
+pub fn open_configured_store(config: Option<&Config>) -> Result<Box<dyn HistoryStore>, String> {
+    let backend = config.map(|c| c.get_history_backend()).unwrap_or_default();
+    open_store(&backend)
+}
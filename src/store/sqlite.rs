@@ -0,0 +1,338 @@
+use crate::models::messages::{SavedMessage, Turn};
+use crate::models::session::{Session, MAIN_SESSION};
+use crate::models::summary::HistorySummary;
+use crate::models::usage::UsageRecord;
+use crate::traits::history_store::HistoryStore;
+use rusqlite::{params, Connection, OptionalExtension};
+use std::path::PathBuf;
+use std::time::Duration;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS turns (
+    profile TEXT NOT NULL,
+    session TEXT NOT NULL,
+    id TEXT NOT NULL,
+    messages TEXT NOT NULL,
+    PRIMARY KEY (profile, session, id)
+);
+CREATE TABLE IF NOT EXISTS sessions (
+    profile TEXT NOT NULL,
+    name TEXT NOT NULL,
+    parent TEXT,
+    forked_at INTEGER NOT NULL,
+    PRIMARY KEY (profile, name)
+);
+CREATE TABLE IF NOT EXISTS summaries (
+    profile TEXT NOT NULL,
+    session TEXT NOT NULL,
+    content TEXT NOT NULL,
+    until TEXT NOT NULL,
+    model TEXT NOT NULL,
+    PRIMARY KEY (profile, session)
+);
+CREATE TABLE IF NOT EXISTS usages (
+    profile TEXT NOT NULL,
+    session TEXT,
+    created INTEGER NOT NULL,
+    model TEXT NOT NULL,
+    prompt_tokens INTEGER NOT NULL,
+    completion_tokens INTEGER NOT NULL,
+    total_tokens INTEGER NOT NULL
+);
+";
+
+/// Stores everything in a single SQLite database file.
+pub struct SqliteHistoryStore {
+    connection: Connection,
+}
+
+fn to_err(action: &str) -> impl Fn(rusqlite::Error) -> String + '_ {
+    move |e| format!("failed to {action}: {e}")
+}
+
+fn session_key(session_name: Option<&str>) -> &str {
+    session_name.unwrap_or(MAIN_SESSION)
+}
+
+impl SqliteHistoryStore {
+    pub fn open(path: &PathBuf) -> Result<SqliteHistoryStore, String> {
+        if let Some(dir) = path.parent() {
+            crate::fs::create_dir_private(dir).map_err(|e| {
+                format!(
+                    "failed to create parent directory of path: path={}, err={}",
+                    path.display(),
+                    e
+                )
+            })?;
+        }
+        let is_new = !path.exists();
+        let connection = Connection::open(path).map_err(|e| {
+            format!(
+                "failed to open database: path={}, err={}",
+                path.display(),
+                e
+            )
+        })?;
+        if is_new {
+            crate::fs::set_mode(path, crate::fs::MODE_PRIVATE_FILE)?;
+        }
+        connection
+            .busy_timeout(Duration::from_secs(10))
+            .map_err(to_err("set busy timeout"))?;
+        connection
+            .execute_batch(SCHEMA)
+            .map_err(to_err("create tables"))?;
+        Ok(SqliteHistoryStore { connection })
+    }
+
+    fn load_turn_rows(
+        &self,
+        profile_name: &str,
+        session_name: Option<&str>,
+    ) -> Result<Vec<Result<Turn, String>>, String> {
+        let mut statement = self
+            .connection
+            .prepare(
+                "SELECT id, messages FROM turns WHERE profile = ?1 AND session = ?2 ORDER BY id",
+            )
+            .map_err(to_err("load turns"))?;
+        let rows = statement
+            .query_map(params![profile_name, session_key(session_name)], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+            })
+            .map_err(to_err("load turns"))?;
+
+        let mut result = Vec::new();
+        for row in rows {
+            let (id, messages) = row.map_err(to_err("load turns"))?;
+            result.push(
+                serde_json::from_str::<Vec<SavedMessage>>(&messages)
+                    .map(|messages| Turn {
+                        id: id.clone(),
+                        messages,
+                    })
+                    .map_err(|e| {
+                        format!("failed to deserialize json: profile={profile_name}, turn={id}, err={e}")
+                    }),
+            );
+        }
+        Ok(result)
+    }
+}
+
+impl HistoryStore for SqliteHistoryStore {
+    fn load_turns(
+        &self,
+        profile_name: &str,
+        session_name: Option<&str>,
+    ) -> Result<Vec<Turn>, String> {
+        let mut result = Vec::new();
+        for row in self.load_turn_rows(profile_name, session_name)? {
+            result.push(row?);
+        }
+        Ok(result)
+    }
+
+    fn load_turns_lenient(
+        &self,
+        profile_name: &str,
+        session_name: Option<&str>,
+    ) -> Result<(Vec<Turn>, Vec<String>), String> {
+        let mut result = Vec::new();
+        let mut errors = Vec::new();
+        for row in self.load_turn_rows(profile_name, session_name)? {
+            match row {
+                Ok(turn) => result.push(turn),
+                Err(e) => errors.push(e),
+            }
+        }
+        Ok((result, errors))
+    }
+
+    fn save_turn(
+        &self,
+        profile_name: &str,
+        session_name: Option<&str>,
+        turn: &Turn,
+    ) -> Result<(), String> {
+        let messages = serde_json::to_string(&turn.messages)
+            .map_err(|e| format!("failed to serialize json: {e}"))?;
+        self.connection
+            .execute(
+                "INSERT OR REPLACE INTO turns (profile, session, id, messages) VALUES (?1, ?2, ?3, ?4)",
+                params![profile_name, session_key(session_name), turn.id, messages],
+            )
+            .map_err(to_err("save turn"))?;
+        Ok(())
+    }
+
+    fn load_summary(
+        &self,
+        profile_name: &str,
+        session_name: Option<&str>,
+    ) -> Result<Option<HistorySummary>, String> {
+        self.connection
+            .query_row(
+                "SELECT content, until, model FROM summaries WHERE profile = ?1 AND session = ?2",
+                params![profile_name, session_key(session_name)],
+                |row| {
+                    Ok(HistorySummary {
+                        content: row.get(0)?,
+                        until: row.get(1)?,
+                        model: row.get(2)?,
+                    })
+                },
+            )
+            .optional()
+            .map_err(to_err("load summary"))
+    }
+
+    fn save_summary(
+        &self,
+        profile_name: &str,
+        session_name: Option<&str>,
+        summary: &HistorySummary,
+    ) -> Result<(), String> {
+        self.connection
+            .execute(
+                "INSERT OR REPLACE INTO summaries (profile, session, content, until, model) VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![
+                    profile_name,
+                    session_key(session_name),
+                    summary.content,
+                    summary.until,
+                    summary.model
+                ],
+            )
+            .map_err(to_err("save summary"))?;
+        Ok(())
+    }
+
+    fn load_sessions(&self, profile_name: &str) -> Result<Vec<Session>, String> {
+        let mut statement = self
+            .connection
+            .prepare(
+                "SELECT name, parent, forked_at FROM sessions WHERE profile = ?1 ORDER BY name",
+            )
+            .map_err(to_err("load sessions"))?;
+        let rows = statement
+            .query_map(params![profile_name], |row| {
+                Ok(Session {
+                    name: row.get(0)?,
+                    parent: row.get(1)?,
+                    forked_at: row.get(2)?,
+                })
+            })
+            .map_err(to_err("load sessions"))?;
+        rows.collect::<Result<Vec<_>, _>>()
+            .map_err(to_err("load sessions"))
+    }
+
+    fn save_session(&self, profile_name: &str, session: &Session) -> Result<(), String> {
+        self.connection
+            .execute(
+                "INSERT OR REPLACE INTO sessions (profile, name, parent, forked_at) VALUES (?1, ?2, ?3, ?4)",
+                params![profile_name, session.name, session.parent, session.forked_at],
+            )
+            .map_err(to_err("save session"))?;
+        Ok(())
+    }
+
+    fn load_usages(&self, profile_name: &str) -> Result<Vec<UsageRecord>, String> {
+        let mut statement = self
+            .connection
+            .prepare(
+                "SELECT profile, session, created, model, prompt_tokens, completion_tokens, total_tokens \
+                 FROM usages WHERE profile = ?1 ORDER BY created",
+            )
+            .map_err(to_err("load usages"))?;
+        let rows = statement
+            .query_map(params![profile_name], |row| {
+                Ok(UsageRecord {
+                    profile: row.get(0)?,
+                    session: row.get(1)?,
+                    created: row.get(2)?,
+                    model: row.get(3)?,
+                    prompt_tokens: row.get(4)?,
+                    completion_tokens: row.get(5)?,
+                    total_tokens: row.get(6)?,
+                })
+            })
+            .map_err(to_err("load usages"))?;
+        rows.collect::<Result<Vec<_>, _>>()
+            .map_err(to_err("load usages"))
+    }
+
+    fn save_usage(&self, usage: &UsageRecord) -> Result<(), String> {
+        self.connection
+            .execute(
+                "INSERT INTO usages (profile, session, created, model, prompt_tokens, completion_tokens, total_tokens) \
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                params![
+                    usage.profile,
+                    usage.session,
+                    usage.created,
+                    usage.model,
+                    usage.prompt_tokens,
+                    usage.completion_tokens,
+                    usage.total_tokens
+                ],
+            )
+            .map_err(to_err("save usage"))?;
+        Ok(())
+    }
+
+    fn rename_profile(&self, from: &str, to: &str) -> Result<(), String> {
+        if self.list_profiles()?.iter().any(|p| p == to) {
+            return Err(format!("history of profile \"{to}\" already exists"));
+        }
+        self.connection
+            .execute_batch("BEGIN IMMEDIATE")
+            .map_err(to_err("begin transaction"))?;
+        for table in ["turns", "sessions", "summaries", "usages"] {
+            if let Err(e) = self.connection.execute(
+                &format!("UPDATE {table} SET profile = ?1 WHERE profile = ?2"),
+                params![to, from],
+            ) {
+                let _ = self.connection.execute_batch("ROLLBACK");
+                return Err(format!("failed to rename profile: {e}"));
+            }
+        }
+        self.connection
+            .execute_batch("COMMIT")
+            .map_err(to_err("commit transaction"))
+    }
+
+    fn delete_profile(&self, profile_name: &str) -> Result<(), String> {
+        self.connection
+            .execute_batch("BEGIN IMMEDIATE")
+            .map_err(to_err("begin transaction"))?;
+        for table in ["turns", "sessions", "summaries", "usages"] {
+            if let Err(e) = self.connection.execute(
+                &format!("DELETE FROM {table} WHERE profile = ?1"),
+                params![profile_name],
+            ) {
+                let _ = self.connection.execute_batch("ROLLBACK");
+                return Err(format!("failed to delete profile: {e}"));
+            }
+        }
+        self.connection
+            .execute_batch("COMMIT")
+            .map_err(to_err("commit transaction"))
+    }
+
+    fn list_profiles(&self) -> Result<Vec<String>, String> {
+        let mut statement = self
+            .connection
+            .prepare(
+                "SELECT profile FROM turns UNION SELECT profile FROM sessions \
+                 UNION SELECT profile FROM usages ORDER BY profile",
+            )
+            .map_err(to_err("list profiles"))?;
+        let rows = statement
+            .query_map([], |row| row.get(0))
+            .map_err(to_err("list profiles"))?;
+        rows.collect::<Result<Vec<_>, _>>()
+            .map_err(to_err("list profiles"))
+    }
+}
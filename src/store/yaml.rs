@@ -0,0 +1,261 @@
+use crate::models::messages::{RawSavedMessage, SavedMessage, Turn};
+use crate::models::session::Session;
+use crate::models::summary::HistorySummary;
+use crate::models::usage::UsageRecord;
+use crate::path::{
+    get_files_in_dir, get_path_history_dir, get_path_profile_history_dir,
+    get_path_profile_sessions_dir, get_path_profile_usage_file, get_path_session_history_dir,
+    get_path_usage_dir,
+};
+use crate::traits::history_store::HistoryStore;
+use std::path::{Path, PathBuf};
+
+/// Stores every turn as a YAML file under `history/<profile>/`.
+pub struct YamlHistoryStore;
+
+fn get_history_dir(profile_name: &str, session_name: Option<&str>) -> Result<PathBuf, String> {
+    match session_name {
+        None => get_path_profile_history_dir(profile_name),
+        Some(session_name) => get_path_session_history_dir(profile_name, session_name),
+    }
+}
+
+fn get_path_summary_file(directory: &Path) -> PathBuf {
+    directory.join("summary").join("summary.yaml")
+}
+
+fn get_file_stem(path: &Path) -> Result<String, String> {
+    Ok(path
+        .file_stem()
+        .ok_or("failed to get filename".to_string())?
+        .to_str()
+        .ok_or("failed to get string of filename".to_string())?
+        .to_string())
+}
+
+fn load_yaml<T: serde::de::DeserializeOwned>(path: &PathBuf) -> Result<T, String> {
+    let text = crate::fs::load_text(path)?;
+    serde_yaml::from_str(&text).map_err(|e| {
+        format!(
+            "failed to deserialize yaml: path={}, err={}",
+            path.display(),
+            e
+        )
+    })
+}
+
+fn save_yaml<T: serde::Serialize>(path: &Path, value: &T) -> Result<(), String> {
+    let text =
+        serde_yaml::to_string(value).map_err(|e| format!("failed to serialize yaml: {e}"))?;
+    crate::fs::save_text(path, &text)
+}
+
+pub fn load_turn_file(path: &PathBuf) -> Result<Turn, String> {
+    let raw_messages: Vec<RawSavedMessage> = load_yaml(path)?;
+    let mut messages = Vec::new();
+    for (i, message) in raw_messages.into_iter().enumerate() {
+        messages.push(
+            message
+                .try_into()
+                .map_err(|e| format!("{e}: path={}, entry={}", path.display(), i + 1))?,
+        );
+    }
+    Ok(Turn {
+        id: get_file_stem(path)?,
+        messages,
+    })
+}
+
+impl HistoryStore for YamlHistoryStore {
+    fn load_turns(
+        &self,
+        profile_name: &str,
+        session_name: Option<&str>,
+    ) -> Result<Vec<Turn>, String> {
+        let directory = get_history_dir(profile_name, session_name)?;
+
+        let mut result = Vec::new();
+        for path in get_files_in_dir(&directory)? {
+            result.push(load_turn_file(&path)?);
+        }
+        Ok(result)
+    }
+
+    fn load_turns_lenient(
+        &self,
+        profile_name: &str,
+        session_name: Option<&str>,
+    ) -> Result<(Vec<Turn>, Vec<String>), String> {
+        let directory = get_history_dir(profile_name, session_name)?;
+
+        let mut result = Vec::new();
+        let mut errors = Vec::new();
+        for path in get_files_in_dir(&directory)? {
+            match load_turn_file(&path) {
+                Ok(turn) => result.push(turn),
+                Err(e) => errors.push(e),
+            }
+        }
+        Ok((result, errors))
+    }
+
+    fn save_turn(
+        &self,
+        profile_name: &str,
+        session_name: Option<&str>,
+        turn: &Turn,
+    ) -> Result<(), String> {
+        let directory = get_history_dir(profile_name, session_name)?;
+        let path = directory.join(format!("{}.yaml", turn.id));
+        let raw_messages: Vec<RawSavedMessage> = turn
+            .messages
+            .iter()
+            .map(|m: &SavedMessage| m.clone().into())
+            .collect();
+        save_yaml(&path, &raw_messages)
+    }
+
+    fn load_summary(
+        &self,
+        profile_name: &str,
+        session_name: Option<&str>,
+    ) -> Result<Option<HistorySummary>, String> {
+        let path = get_path_summary_file(&get_history_dir(profile_name, session_name)?);
+        if !path.is_file() {
+            return Ok(None);
+        }
+        load_yaml(&path).map(Some)
+    }
+
+    fn save_summary(
+        &self,
+        profile_name: &str,
+        session_name: Option<&str>,
+        summary: &HistorySummary,
+    ) -> Result<(), String> {
+        let path = get_path_summary_file(&get_history_dir(profile_name, session_name)?);
+        save_yaml(&path, summary)
+    }
+
+    fn load_sessions(&self, profile_name: &str) -> Result<Vec<Session>, String> {
+        let directory = get_path_profile_sessions_dir(profile_name)?;
+
+        let mut result = Vec::new();
+        for path in get_files_in_dir(&directory)? {
+            result.push(load_yaml(&path)?);
+        }
+        Ok(result)
+    }
+
+    fn save_session(&self, profile_name: &str, session: &Session) -> Result<(), String> {
+        let path =
+            get_path_profile_sessions_dir(profile_name)?.join(format!("{}.yaml", session.name));
+        save_yaml(&path, session)
+    }
+
+    fn load_usages(&self, profile_name: &str) -> Result<Vec<UsageRecord>, String> {
+        let path = get_path_profile_usage_file(profile_name)?;
+        if !path.is_file() {
+            return Ok(Vec::new());
+        }
+
+        let mut result = Vec::new();
+        for line in crate::fs::load_text(&path)?.lines() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            result.push(serde_json::from_str(line).map_err(|e| {
+                format!(
+                    "failed to deserialize json: path={}, err={}",
+                    path.display(),
+                    e
+                )
+            })?);
+        }
+        Ok(result)
+    }
+
+    fn save_usage(&self, usage: &UsageRecord) -> Result<(), String> {
+        let path = get_path_profile_usage_file(&usage.profile)?;
+        let line =
+            serde_json::to_string(usage).map_err(|e| format!("failed to serialize json: {e}"))?;
+        crate::fs::append_text(&path, &format!("{line}\n"))
+    }
+
+    fn rename_profile(&self, from: &str, to: &str) -> Result<(), String> {
+        let history_from = get_path_profile_history_dir(from)?;
+        let history_to = get_path_profile_history_dir(to)?;
+        let usage_to = get_path_profile_usage_file(to)?;
+        if history_to.exists() || usage_to.exists() {
+            return Err(format!("history of profile \"{to}\" already exists"));
+        }
+
+        if history_from.is_dir() {
+            std::fs::rename(&history_from, &history_to).map_err(|e| {
+                format!(
+                    "failed to move directory: from={}, to={}, err={}",
+                    history_from.display(),
+                    history_to.display(),
+                    e
+                )
+            })?;
+        }
+
+        // usage records hold the profile name, so they are rewritten rather than moved
+        let usages = self.load_usages(from)?;
+        for mut usage in usages {
+            usage.profile = to.to_string();
+            self.save_usage(&usage)?;
+        }
+        remove_file_if_exists(&get_path_profile_usage_file(from)?)
+    }
+
+    fn delete_profile(&self, profile_name: &str) -> Result<(), String> {
+        let history_dir = get_path_profile_history_dir(profile_name)?;
+        if history_dir.is_dir() {
+            std::fs::remove_dir_all(&history_dir).map_err(|e| {
+                format!(
+                    "failed to remove directory: path={}, err={}",
+                    history_dir.display(),
+                    e
+                )
+            })?;
+        }
+        remove_file_if_exists(&get_path_profile_usage_file(profile_name)?)
+    }
+
+    fn list_profiles(&self) -> Result<Vec<String>, String> {
+        let mut result = Vec::new();
+
+        let history_dir = get_path_history_dir()?;
+        if history_dir.is_dir() {
+            let entries = std::fs::read_dir(&history_dir)
+                .map_err(|e| format!("failed to read directory: {e}"))?;
+            for entry in entries {
+                let entry = entry
+                    .map_err(|e| format!("failed to parse result of reading directory: {e}"))?;
+                if entry.path().is_dir() {
+                    result.push(get_file_stem(&entry.path())?);
+                }
+            }
+        }
+
+        for path in get_files_in_dir(&get_path_usage_dir()?)? {
+            let name = get_file_stem(&path)?;
+            if !result.contains(&name) {
+                result.push(name);
+            }
+        }
+
+        result.sort();
+        Ok(result)
+    }
+}
+
+fn remove_file_if_exists(path: &PathBuf) -> Result<(), String> {
+    if path.is_file() {
+        std::fs::remove_file(path)
+            .map_err(|e| format!("failed to remove file: path={}, err={}", path.display(), e))?;
+    }
+    Ok(())
+}
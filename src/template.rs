@@ -0,0 +1,144 @@
+use std::collections::BTreeMap;
+
+/// Replace `{{name}}` in `text`. Variables are looked up in `vars` first, then in the builtins:
+/// `date`, `datetime`, `cwd`, `git_branch` and `env.NAME`. `\{{` is left as a literal `{{`.
+pub fn render(text: &str, vars: &BTreeMap<String, String>) -> Result<String, String> {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find("{{") {
+        if rest[..start].ends_with('\\') {
+            result.push_str(&rest[..start - 1]);
+            result.push_str("{{");
+            rest = &rest[start + 2..];
+            continue;
+        }
+
+        let Some(length) = rest[start + 2..].find("}}") else {
+            break;
+        };
+        let name = rest[start + 2..start + 2 + length].trim();
+
+        result.push_str(&rest[..start]);
+        if is_variable_name(name) {
+            result.push_str(&lookup(name, vars)?);
+        } else {
+            result.push_str(&rest[start..start + 2 + length + 2]);
+        }
+        rest = &rest[start + 2 + length + 2..];
+    }
+    result.push_str(rest);
+
+    Ok(result)
+}
+
+fn is_variable_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {}
+        _ => return false,
+    }
+    chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.' || c == '-')
+}
+
+fn lookup(name: &str, vars: &BTreeMap<String, String>) -> Result<String, String> {
+    if let Some(value) = vars.get(name) {
+        return Ok(value.clone());
+    }
+
+    if let Some(env_name) = name.strip_prefix("env.") {
+        return std::env::var(env_name).map_err(|e| {
+            format!("failed to resolve template variable \"{name}\": environment variable \"{env_name}\": {e}")
+        });
+    }
+
+    match name {
+        "date" => Ok(chrono::Local::now().format("%Y-%m-%d").to_string()),
+        "datetime" => Ok(chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string()),
+        "cwd" => std::env::current_dir()
+            .map(|p| p.display().to_string())
+            .map_err(|e| format!("failed to resolve template variable \"cwd\": {e}")),
+        "git_branch" => get_git_branch(),
+        _ => Err(format!(
+            "template variable \"{name}\" is not defined (set it with `--var {name}=VALUE` or in `vars` of profile)"
+        )),
+    }
+}
+
+fn get_git_branch() -> Result<String, String> {
+    let output = std::process::Command::new("git")
+        .args(["rev-parse", "--abbrev-ref", "HEAD"])
+        .output()
+        .map_err(|e| format!("failed to resolve template variable \"git_branch\": {e}"))?;
+    if !output.status.success() {
+        return Err(format!(
+            "failed to resolve template variable \"git_branch\": {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Parse `KEY=VALUE` of `--var`.
+pub fn parse_var(text: &str) -> Result<(String, String), String> {
+    let (key, value) = text
+        .split_once('=')
+        .ok_or(format!("invalid variable \"{text}\" (expected KEY=VALUE)"))?;
+    let key = key.trim();
+    if !is_variable_name(key) {
+        return Err(format!("invalid variable name \"{key}\""));
+    }
+    Ok((key.to_string(), value.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars() -> BTreeMap<String, String> {
+        BTreeMap::from([("lang".to_string(), "Rust".to_string())])
+    }
+
+    #[test]
+    fn render_replaces_variables() {
+        assert_eq!(
+            render("written in {{lang}} and {{ lang }}", &vars()),
+            Ok("written in Rust and Rust".to_string())
+        );
+    }
+
+    #[test]
+    fn render_rejects_undefined_variable() {
+        let err = render("hello {{name}}", &vars()).unwrap_err();
+        assert!(
+            err.contains("template variable \"name\" is not defined"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn render_keeps_escaped_braces() {
+        assert_eq!(
+            render("\\{{name}} is {{lang}}", &vars()),
+            Ok("{{name}} is Rust".to_string())
+        );
+    }
+
+    #[test]
+    fn render_keeps_braces_which_are_not_variables() {
+        assert_eq!(
+            render("{{ 1 + 2 }} and {{", &vars()),
+            Ok("{{ 1 + 2 }} and {{".to_string())
+        );
+    }
+
+    #[test]
+    fn parse_var_splits_key_and_value() {
+        assert_eq!(
+            parse_var("lang=Rust=2021"),
+            Ok(("lang".to_string(), "Rust=2021".to_string()))
+        );
+        assert!(parse_var("lang").is_err());
+        assert!(parse_var("1lang=Rust").is_err());
+    }
+}
@@ -0,0 +1,157 @@
+use crate::models::api::ToolCall;
+use crate::models::config::Profile;
+use crate::models::tool::ToolDefinition;
+use crate::path::{get_files_in_dir, get_path_profile_tools_dir};
+use std::io::{IsTerminal, Write};
+use std::path::PathBuf;
+use std::process::Stdio;
+
+const TOOL_CHOICES: [&str; 3] = ["none", "auto", "required"];
+
+/// Tools declared in the tools dir of the profile, and of its source profiles when
+/// `inherit_pre_messages` is set. A tool overrides the one of the same name declared before.
+pub fn get_tools(profile: &Profile) -> Result<Vec<ToolDefinition>, String> {
+    let mut directories = Vec::new();
+    if profile.get_inherit_pre_messages() {
+        for name in profile.resolved_sources.iter().rev() {
+            directories.push(get_path_profile_tools_dir(name)?);
+        }
+    }
+    directories.push(get_path_profile_tools_dir(&profile.name)?);
+
+    let mut result: Vec<ToolDefinition> = Vec::new();
+    for directory in directories {
+        for path in get_files_in_dir(&directory)? {
+            for tool in load_tool_file(&path)? {
+                result.retain(|t| t.name != tool.name);
+                result.push(tool);
+            }
+        }
+    }
+    Ok(result)
+}
+
+/// A tools file (JSON or YAML) holds a tool or a list of them.
+pub fn load_tool_file(path: &PathBuf) -> Result<Vec<ToolDefinition>, String> {
+    let extension = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_string())
+        .unwrap_or_default();
+    if !["json", "yaml", "yml"].contains(&extension.as_str()) {
+        return Err(format!(
+            "unsupported tools file (json, yaml or yml): path={}",
+            path.display()
+        ));
+    }
+
+    let text = crate::fs::load_text(path)?;
+    let to_error = |e: serde_yaml::Error| {
+        format!(
+            "failed to deserialize tools file: path={}, err={}",
+            path.display(),
+            e
+        )
+    };
+    // JSON is also read as YAML
+    let value: serde_yaml::Value = serde_yaml::from_str(&text).map_err(to_error)?;
+    let tools: Vec<ToolDefinition> = if value.is_sequence() {
+        serde_yaml::from_value(value).map_err(to_error)?
+    } else {
+        vec![serde_yaml::from_value(value).map_err(to_error)?]
+    };
+
+    for tool in &tools {
+        if tool.name.is_empty()
+            || tool.name.len() > 64
+            || !tool
+                .name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        {
+            return Err(format!(
+                "invalid tool name \"{}\" (1-64 letters, digits, '_' or '-'): path={}",
+                tool.name,
+                path.display()
+            ));
+        }
+        if tool.command.as_ref().is_some_and(|c| c.is_empty()) {
+            return Err(format!(
+                "command of tool \"{}\" is empty: path={}",
+                tool.name,
+                path.display()
+            ));
+        }
+    }
+    Ok(tools)
+}
+
+/// `tool_choice` of the request from `--tool-choice`, which is a keyword or a tool name.
+pub fn parse_tool_choice(
+    choice: &str,
+    tools: &[ToolDefinition],
+) -> Result<serde_json::Value, String> {
+    if TOOL_CHOICES.contains(&choice) {
+        return Ok(serde_json::json!(choice));
+    }
+    if tools.iter().any(|t| t.name == choice) {
+        return Ok(serde_json::json!({"type": "function", "function": {"name": choice}}));
+    }
+    let candidates = TOOL_CHOICES
+        .into_iter()
+        .chain(tools.iter().map(|t| t.name.as_str()));
+    let hint = match crate::migration::did_you_mean(choice, candidates) {
+        Some(candidate) => format!(" (did you mean \"{candidate}\"?)"),
+        None => "".to_string(),
+    };
+    Err(format!(
+        "unknown tool choice \"{choice}\"{hint}; use none, auto, required or a tool name"
+    ))
+}
+
+/// Result of a tool call to send back to the model. Failures of the tool itself are
+/// returned as the result so that the model can see them.
+pub fn run_tool(tools: &[ToolDefinition], tool_call: &ToolCall) -> Result<String, String> {
+    let name = &tool_call.function.name;
+    let arguments = &tool_call.function.arguments;
+    eprintln!("tool call: {name}({arguments})");
+
+    let Some(tool) = tools.iter().find(|t| &t.name == name) else {
+        eprintln!("warning: model called unknown tool \"{name}\"");
+        return Ok(format!("error: unknown tool \"{name}\""));
+    };
+    match &tool.command {
+        Some(command) => run_command(name, command, arguments),
+        None => {
+            if !std::io::stdin().is_terminal() {
+                return Err(format!(
+                    "tool \"{name}\" has no command, and its result cannot be asked as stdin is not a terminal"
+                ));
+            }
+            crate::input::get_input(format!("result of {name}: "))
+        }
+    }
+}
+
+fn run_command(name: &str, command: &[String], arguments: &str) -> Result<String, String> {
+    let mut child = std::process::Command::new(&command[0])
+        .args(&command[1..])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("failed to run command of tool \"{name}\": {e}"))?;
+    // a command which does not read the arguments closes stdin early
+    let _ = child.stdin.take().unwrap().write_all(arguments.as_bytes());
+    let output = child
+        .wait_with_output()
+        .map_err(|e| format!("failed to run command of tool \"{name}\": {e}"))?;
+
+    let stdout = String::from_utf8_lossy(&output.stdout)
+        .trim_end()
+        .to_string();
+    if output.status.success() {
+        Ok(stdout)
+    } else {
+        eprintln!("warning: tool \"{name}\" failed: {}", output.status);
+        Ok(format!("error: tool failed ({}): {stdout}", output.status))
+    }
+}
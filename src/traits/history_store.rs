@@ -0,0 +1,61 @@
+use crate::models::messages::Turn;
+use crate::models::session::Session;
+use crate::models::summary::HistorySummary;
+use crate::models::usage::UsageRecord;
+
+/// Storage of histories, sessions, summaries and usage records.
+/// `session_name: None` means the main history of the profile.
+pub trait HistoryStore {
+    fn load_turns(
+        &self,
+        profile_name: &str,
+        session_name: Option<&str>,
+    ) -> Result<Vec<Turn>, String>;
+    fn save_turn(
+        &self,
+        profile_name: &str,
+        session_name: Option<&str>,
+        turn: &Turn,
+    ) -> Result<(), String>;
+    fn load_summary(
+        &self,
+        profile_name: &str,
+        session_name: Option<&str>,
+    ) -> Result<Option<HistorySummary>, String>;
+    fn save_summary(
+        &self,
+        profile_name: &str,
+        session_name: Option<&str>,
+        summary: &HistorySummary,
+    ) -> Result<(), String>;
+    fn load_sessions(&self, profile_name: &str) -> Result<Vec<Session>, String>;
+    fn save_session(&self, profile_name: &str, session: &Session) -> Result<(), String>;
+    fn load_usages(&self, profile_name: &str) -> Result<Vec<UsageRecord>, String>;
+    fn save_usage(&self, usage: &UsageRecord) -> Result<(), String>;
+    /// names of profiles which have any stored data
+    fn list_profiles(&self) -> Result<Vec<String>, String>;
+    /// Move all data of `from` to `to`, which must not have any data.
+    fn rename_profile(&self, from: &str, to: &str) -> Result<(), String>;
+    fn delete_profile(&self, profile_name: &str) -> Result<(), String>;
+
+    /// Like `load_turns`, but broken turns are skipped and returned as error messages.
+    fn load_turns_lenient(
+        &self,
+        profile_name: &str,
+        session_name: Option<&str>,
+    ) -> Result<(Vec<Turn>, Vec<String>), String> {
+        self.load_turns(profile_name, session_name)
+            .map(|turns| (turns, Vec::new()))
+    }
+
+    fn load_session(
+        &self,
+        profile_name: &str,
+        session_name: &str,
+    ) -> Result<Option<Session>, String> {
+        Ok(self
+            .load_sessions(profile_name)?
+            .into_iter()
+            .find(|s| s.name == session_name))
+    }
+}
@@ -1 +1,2 @@
 pub mod command_definition;
+pub mod history_store;